@@ -0,0 +1,157 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use std::path::PathBuf;
+
+use crate::site::helper::Verbosity;
+
+pub const USAGE: &str = "Usage: flow-web [COMMAND] [OPTIONS]
+
+Commands:
+  build            Generate the site (default)
+  serve            Generate the site, serve it at http://127.0.0.1:8080/ and rebuild on changes
+  new <TITLE>      Create a new draft blog post in 'blog-posts'
+  check            Generate the site into a temporary dir to find errors, output and cache are left untouched
+  clean            Remove the output dir and the build cache
+
+Options:
+  --source <DIR>   Dir with the site sources ('blog-posts', 'screenshots', ...) [default: .]
+  --output <DIR>   Dir to generate the site in [default: <source>/output]
+  --config <FILE>  Site config [default: <source>/site.conf]
+  --drafts         Include blog posts with 'draft: true'
+  -q, --quiet      Only print errors
+  -v, --verbose    Print everything, including created dirs and unchanged files
+  -h, --help       Print this help";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Build,
+    Serve,
+    New(String),
+    Check,
+    Clean,
+    Help,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub source: PathBuf,
+    pub output: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub drafts: bool,
+    pub verbosity: Verbosity,
+}
+
+/// Parses the command line arguments (without the program name), returns an error message
+/// for invalid usage.
+pub fn parse_args(args: Vec<String>) -> Result<Cli, String> {
+    let mut cli = Cli {
+        command: Command::Build,
+        source: PathBuf::from("."),
+        output: None,
+        config: None,
+        drafts: false,
+        verbosity: Verbosity::Normal,
+    };
+    let mut is_help = false;
+    let mut command: Option<String> = None;
+    let mut command_args: Vec<String> = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // options with a value accept both '--output dir' and '--output=dir':
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                (String::from(name), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+
+        match name.as_str() {
+            "--source" | "--output" | "--config" => {
+                let value = match inline_value.or_else(|| args.next()) {
+                    Some(value) if !value.is_empty() => PathBuf::from(value),
+                    _ => return Err(format!("Missing value for option '{}'.", name)),
+                };
+                match name.as_str() {
+                    "--source" => cli.source = value,
+                    "--output" => cli.output = Some(value),
+                    _ => cli.config = Some(value),
+                }
+            }
+            "--drafts" | "--quiet" | "--verbose" | "--help" if inline_value.is_some() => {
+                return Err(format!("Option '{}' doesn't take a value.", name));
+            }
+            "--drafts" => cli.drafts = true,
+            "-q" | "--quiet" => cli.verbosity = Verbosity::Quiet,
+            "-v" | "--verbose" => cli.verbosity = Verbosity::Verbose,
+            "-h" | "--help" => is_help = true,
+            _ if name.starts_with('-') => return Err(format!("Unknown option '{}'.", name)),
+            _ if command.is_none() => command = Some(arg),
+            _ => command_args.push(arg),
+        }
+    }
+
+    // help is a flag, e.g. 'flow-web -h build' prints the help instead of building:
+    if is_help {
+        cli.command = Command::Help;
+        return Ok(cli);
+    }
+
+    cli.command = match command.as_deref() {
+        None | Some("build") => Command::Build,
+        Some("serve") => Command::Serve,
+        Some("new") => {
+            let title = command_args.join(" ");
+            if title.trim().is_empty() {
+                return Err(String::from("Missing title for command 'new'."));
+            }
+            command_args.clear();
+            Command::New(title)
+        }
+        Some("check") => Command::Check,
+        Some("clean") => Command::Clean,
+        Some("help") => Command::Help,
+        Some(value) => return Err(format!("Unknown command '{}'.", value)),
+    };
+
+    if let Some(arg) = command_args.first() {
+        return Err(format!("Unexpected argument '{}'.", arg));
+    }
+
+    return Ok(cli);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        return parse_args(args.iter().map(|arg| arg.to_string()).collect());
+    }
+
+    #[test]
+    fn help_is_a_flag() {
+        for args in [
+            &["-h"][..],
+            &["--help"],
+            &["-h", "build"],
+            &["serve", "--help"],
+        ] {
+            assert_eq!(parse(args).unwrap().command, Command::Help);
+        }
+        assert_eq!(parse(&["help"]).unwrap().command, Command::Help);
+        assert!(parse(&["-h", "--unknown"]).is_err());
+    }
+
+    #[test]
+    fn boolean_options_reject_values() {
+        for arg in ["--drafts=x", "--quiet=x", "--verbose=", "--help=x"] {
+            assert!(parse(&[arg]).is_err(), "{}", arg);
+        }
+        let cli = parse(&["build", "--drafts", "--output=out"]).unwrap();
+        assert!(cli.drafts);
+        assert_eq!(cli.output, Some(PathBuf::from("out")));
+    }
+}
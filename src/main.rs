@@ -1,15 +1,138 @@
 // luflow.net web site
 // AGPL-3.0 License (see LICENSE)
 
+mod cli;
+mod serve;
 mod site;
 
-use crate::site::generate_site;
+use std::process::ExitCode;
+
+use crate::cli::{Command, USAGE, parse_args};
+use crate::serve::serve_site;
+use crate::site::blog_post::create_blog_post_file;
+use crate::site::helper::{Helper, Verbosity};
+use crate::site::paths::SitePaths;
+use crate::site::{BuildOptions, generate_site};
+
+/// Exit code when generating or checking the site failed.
+const EXIT_FAILURE: u8 = 1;
+
+/// Exit code for invalid command line usage.
+const EXIT_USAGE: u8 = 2;
 
 #[tokio::main]
-async fn main() {
-    // generate all site related files:
-    generate_site().await;
+async fn main() -> ExitCode {
+    let cli = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    Helper::set_verbosity(cli.verbosity);
+
+    if !cli.source.is_dir() {
+        eprintln!(
+            "error: Source dir '{}' does not exist.",
+            cli.source.display()
+        );
+        return ExitCode::from(EXIT_USAGE);
+    }
+
+    let mut paths = SitePaths::new(cli.source.clone());
+    if let Some(output) = &cli.output {
+        paths.output_dir = output.clone();
+    }
+    if let Some(config) = &cli.config {
+        paths.config_file = config.clone();
+    }
+    let options = BuildOptions {
+        paths,
+        use_cache: true,
+        include_drafts: cli.drafts,
+    };
+
+    match cli.command {
+        Command::Help => {
+            println!("{}", USAGE);
+        }
+        Command::Build => {
+            // generate all site related files:
+            if let Err(e) = generate_site(options.clone()).await {
+                eprintln!("\nerror: {}", e);
+                return ExitCode::from(EXIT_FAILURE);
+            }
+
+            Helper::print(
+                Verbosity::Normal,
+                &format!(
+                    "\nDone! Output can be found in '{}' folder.\n(Serve locally: 'flow-web serve')",
+                    options.paths.output_dir.display()
+                ),
+            );
+        }
+        Command::Serve => {
+            // previews the site locally and rebuilds it on changes:
+            if let Err(e) = serve_site(options).await {
+                eprintln!("error: {}", e);
+                return ExitCode::from(EXIT_FAILURE);
+            }
+        }
+        Command::New(title) => match create_blog_post_file(&options.paths, &title) {
+            Ok(path) => Helper::print(
+                Verbosity::Quiet,
+                &format!("Created draft blog post '{}'", path.display()),
+            ),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return ExitCode::from(EXIT_FAILURE);
+            }
+        },
+        Command::Check => {
+            // build everything from scratch into a temporary dir, the real output and
+            // cache are left untouched:
+            let mut check_options = BuildOptions {
+                use_cache: false,
+                ..options
+            };
+            check_options.paths.output_dir =
+                std::env::temp_dir().join(format!("flow-web-check-{}", std::process::id()));
+
+            let output_dir = check_options.paths.output_dir.clone();
+            let result = generate_site(check_options).await;
+            if Helper::exists_dir(&output_dir)
+                && let Err(e) = Helper::remove_dir_all(&output_dir)
+            {
+                eprintln!("error: {}", e);
+            }
+
+            if let Err(e) = result {
+                eprintln!("\nerror: {}", e);
+                return ExitCode::from(EXIT_FAILURE);
+            }
+            Helper::print(Verbosity::Normal, "\nCheck passed, no errors found.");
+        }
+        Command::Clean => {
+            // the cache describes the output dir, so both go:
+            let paths = &options.paths;
+            if Helper::exists_dir(&paths.output_dir)
+                && let Err(e) = Helper::remove_dir_all(&paths.output_dir)
+            {
+                eprintln!("error: {}", e);
+                return ExitCode::from(EXIT_FAILURE);
+            }
+            if paths.cache_file.exists()
+                && let Err(e) = std::fs::remove_file(&paths.cache_file)
+            {
+                eprintln!(
+                    "error: Unable to remove '{}': {}",
+                    paths.cache_file.display(),
+                    e
+                );
+                return ExitCode::from(EXIT_FAILURE);
+            }
+        }
+    }
 
-    println!("\nDone! Output can be found in 'output' folder.");
-    println!("(Serve locally: 'servez output')");
+    return ExitCode::SUCCESS;
 }
@@ -0,0 +1,342 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::site::error::SiteError;
+use crate::site::helper::Helper;
+use crate::site::paths::SitePaths;
+use crate::site::{BuildOptions, generate_site};
+
+/// Address the preview server listens on.
+const SERVE_ADDRESS: &str = "127.0.0.1:8080";
+
+/// Url of the server-sent events stream telling open pages to reload.
+const RELOAD_EVENTS_URL: &str = "/__flow-web/reload";
+
+/// Injected before `</body>` of every served html page, reloads the page when the site
+/// was rebuilt.
+const RELOAD_SCRIPT: &str = "<script>new EventSource('/__flow-web/reload').onmessage = function () { location.reload(); };</script>";
+
+/// Sources (relative to the source dir) that trigger a rebuild when changed, besides the
+/// site config.
+const WATCHED_PATHS: [&str; 8] = [
+    "blog-posts",
+    "screenshots",
+    "projects",
+    "pages",
+    "topics",
+    "static",
+    "static_root",
+    "themes",
+];
+
+/// How often the watched sources are checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Build counter shared with the reload event streams, bumped after every rebuild.
+struct ReloadState {
+    build: Mutex<u64>,
+    rebuilt: Condvar,
+}
+
+/// Builds the site, serves the output dir at http://127.0.0.1:8080/ and rebuilds whenever
+/// a source changes. Open pages reload themselves after each rebuild.
+///
+/// Only returns if the server can't be started.
+pub async fn serve_site(options: BuildOptions) -> Result<(), SiteError> {
+    build_site(&options).await;
+
+    let listener = match TcpListener::bind(SERVE_ADDRESS) {
+        Ok(value) => value,
+        Err(e) => return Err(SiteError::listen(SERVE_ADDRESS, e)),
+    };
+    println!(
+        "\nServing '{}' at http://{}/ (stop with CTRL+c)",
+        options.paths.output_dir.display(),
+        SERVE_ADDRESS
+    );
+
+    let reload_state = Arc::new(ReloadState {
+        build: Mutex::new(0),
+        rebuilt: Condvar::new(),
+    });
+
+    // accept connections on its own thread, one thread per connection:
+    let accept_state = reload_state.clone();
+    let output_dir = options.paths.output_dir.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let state = accept_state.clone();
+            let output_dir = output_dir.clone();
+            std::thread::spawn(move || handle_connection(stream, &output_dir, state));
+        }
+    });
+
+    // poll watched sources and rebuild on change:
+    let mut snapshot = get_sources_snapshot(&options.paths);
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+
+        let new_snapshot = get_sources_snapshot(&options.paths);
+        if new_snapshot == snapshot {
+            continue;
+        }
+        snapshot = new_snapshot;
+
+        println!("\nSources changed, rebuilding...");
+        build_site(&options).await;
+
+        *reload_state.build.lock().unwrap() += 1;
+        reload_state.rebuilt.notify_all();
+    }
+}
+
+async fn build_site(options: &BuildOptions) {
+    // a failing build must not stop the server, keep serving the last output:
+    match generate_site(options.clone()).await {
+        Ok(_) => println!(
+            "\nDone! Output can be found in '{}' folder.",
+            options.paths.output_dir.display()
+        ),
+        Err(e) => eprintln!("\nerror: {}", e),
+    }
+}
+
+/// Modification time and size of every watched file.
+fn get_sources_snapshot(paths: &SitePaths) -> HashMap<PathBuf, (SystemTime, u64)> {
+    let mut snapshot = HashMap::new();
+
+    let watched_paths = WATCHED_PATHS
+        .iter()
+        .map(|path| paths.source_dir.join(path))
+        .chain([paths.config_file.clone()]);
+    for path in watched_paths {
+        let files = if path.is_dir() {
+            Helper::get_files_recursive(&path).unwrap_or_default()
+        } else {
+            vec![path]
+        };
+
+        for file in files {
+            if let Ok(metadata) = std::fs::metadata(&file) {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                snapshot.insert(file, (modified, metadata.len()));
+            }
+        }
+    }
+
+    return snapshot;
+}
+
+fn handle_connection(stream: TcpStream, output_dir: &Path, reload_state: Arc<ReloadState>) {
+    let mut reader = BufReader::new(&stream);
+
+    // request line, e.g. 'GET /blog/ HTTP/1.1':
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let v: Vec<&str> = request_line.split_whitespace().collect();
+    let (method, target) = match (v.first(), v.get(1)) {
+        (Some(method), Some(target)) => (*method, *target),
+        _ => return,
+    };
+
+    // skip headers, nothing in them is needed:
+    let mut header = String::new();
+    while reader.read_line(&mut header).is_ok_and(|n| n > 0) && header.trim() != "" {
+        header.clear();
+    }
+
+    if method != "GET" && method != "HEAD" {
+        write_response(
+            &stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"",
+            method == "HEAD",
+        );
+        return;
+    }
+
+    let path = percent_decode(target.split(['?', '#']).next().unwrap_or("/"));
+    if path == RELOAD_EVENTS_URL {
+        stream_reload_events(stream, reload_state);
+        return;
+    }
+
+    serve_file(&stream, output_dir, target, &path, method == "HEAD");
+}
+
+/// Serves the file at the percent-decoded `path` of request `target`.
+fn serve_file(stream: &TcpStream, output_dir: &Path, target: &str, path: &str, is_head: bool) {
+    // never serve anything outside the output dir:
+    let relative_path = Path::new(path.trim_start_matches('/'));
+    if relative_path
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        write_not_found(stream, output_dir, is_head);
+        return;
+    }
+
+    let mut file_path = output_dir.join(relative_path);
+    if file_path.is_dir() {
+        // directories are served by their index, with a trailing slash so relative links work:
+        if !path.ends_with('/') {
+            // the raw target is still percent-encoded, the query stays after the slash:
+            let (target_path, query) =
+                target.split_at(target.find(['?', '#']).unwrap_or(target.len()));
+            let location = format!("{}/{}", target_path, query);
+            let header = format!(
+                "HTTP/1.1 301 Moved Permanently\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                location
+            );
+            let mut stream = stream;
+            let _ = stream.write_all(header.as_bytes());
+            return;
+        }
+        file_path = file_path.join("index.html");
+    }
+
+    match std::fs::read(&file_path) {
+        Ok(data) => {
+            let mime_type = get_mime_type(&file_path);
+            write_response(
+                stream,
+                "200 OK",
+                mime_type,
+                &inject_reload_script(data, mime_type),
+                is_head,
+            );
+        }
+        _ => write_not_found(stream, output_dir, is_head),
+    }
+}
+
+fn write_not_found(stream: &TcpStream, output_dir: &Path, is_head: bool) {
+    // use generated error page if available:
+    let data = std::fs::read(output_dir.join("404.html")).unwrap_or(b"404 Not Found".to_vec());
+    write_response(
+        stream,
+        "404 Not Found",
+        "text/html; charset=utf-8",
+        &inject_reload_script(data, "text/html; charset=utf-8"),
+        is_head,
+    );
+}
+
+fn write_response(stream: &TcpStream, status: &str, mime_type: &str, body: &[u8], is_head: bool) {
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        mime_type,
+        body.len()
+    );
+
+    let mut stream = stream;
+    if stream.write_all(header.as_bytes()).is_ok() && !is_head {
+        let _ = stream.write_all(body);
+    }
+}
+
+/// Keeps the connection open and sends an event after every rebuild, until the page is
+/// closed.
+fn stream_reload_events(mut stream: TcpStream, reload_state: Arc<ReloadState>) {
+    let header = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n";
+    if stream.write_all(header.as_bytes()).is_err() {
+        return;
+    }
+
+    let mut build = *reload_state.build.lock().unwrap();
+    loop {
+        let lock = reload_state.build.lock().unwrap();
+        let (lock, _) = reload_state
+            .rebuilt
+            .wait_timeout_while(lock, Duration::from_secs(15), |value| *value == build)
+            .unwrap();
+
+        // send a comment when nothing happened, to find out if the page was closed:
+        let message = if *lock != build {
+            "data: reload\n\n"
+        } else {
+            ": ping\n\n"
+        };
+        build = *lock;
+        drop(lock);
+
+        if stream.write_all(message.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+fn inject_reload_script(data: Vec<u8>, mime_type: &str) -> Vec<u8> {
+    if !mime_type.starts_with("text/html") {
+        return data;
+    }
+
+    let html = String::from_utf8_lossy(&data);
+    return match html.rfind("</body>") {
+        Some(i) => format!("{}{}{}", &html[..i], RELOAD_SCRIPT, &html[i..]).into_bytes(),
+        _ => format!("{}{}", html, RELOAD_SCRIPT).into_bytes(),
+    };
+}
+
+fn get_mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|value| value.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    return match extension.as_str() {
+        "html" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "atom" => "application/atom+xml",
+        "rss" => "application/rss+xml",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        "webmanifest" => "application/manifest+json",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    };
+}
+
+/// Decodes '%XX' escapes in an url path, e.g. '/blog/a%20b/' -> '/blog/a b/'.
+fn percent_decode(str: &str) -> String {
+    let bytes = str.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            decoded.push(u8::from_str_radix(&str[i + 1..i + 3], 16).unwrap());
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    return String::from_utf8_lossy(&decoded).into_owned();
+}
@@ -3,10 +3,27 @@
 
 pub mod blog;
 pub mod blog_post;
+pub mod build_cache;
+pub mod config;
 pub mod core;
+pub mod error;
+pub mod feed;
+pub mod gallery;
 pub mod helper;
+pub mod menu;
+pub mod page;
+pub mod paths;
+pub mod project;
+pub mod projects;
 pub mod screenshot;
 pub mod screenshots;
+pub mod search;
+pub mod sitemap;
+pub mod taxonomy;
+pub mod template;
+pub mod theme;
+pub mod topic;
+pub mod xml_writer;
 
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -14,59 +31,255 @@ use std::sync::Arc;
 use crate::site::blog::BlogShared;
 use crate::site::blog::generate_blog;
 use crate::site::blog::get_latest_blog_posts;
+use crate::site::build_cache::BuildCache;
+use crate::site::config::parse_config_file;
 use crate::site::core::CoreShared;
 use crate::site::core::generate_core;
 use crate::site::core::generate_root_index;
+use crate::site::error::SiteError;
 use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::page::parse_pages;
+use crate::site::paths::SitePaths;
+use crate::site::projects::generate_projects;
+use crate::site::projects::parse_projects;
 use crate::site::screenshots::ScreenshotsShared;
 use crate::site::screenshots::generate_screenshots;
+use crate::site::screenshots::parse_galleries;
+use crate::site::sitemap::SitemapShared;
+use crate::site::sitemap::generate_sitemap;
+use crate::site::theme::Theme;
 
-pub async fn generate_site() {
-    // make sure 'templates', 'screenshots' and 'blog-posts' dirs exists:
-    // (this tool must be executed in root folder)
-    if !Helper::exists_dir(&PathBuf::new().join("blog-posts"))
-        || !Helper::exists_dir(&PathBuf::new().join("screenshots"))
-        || !Helper::exists_dir(&PathBuf::new().join("templates"))
+/// Settings of a single build, given on the command line.
+#[derive(Clone, Debug)]
+pub struct BuildOptions {
+    /// Where the site is read from and generated to.
+    pub paths: SitePaths,
+    /// Only render and write what changed since the last build, everything is rebuilt
+    /// otherwise.
+    pub use_cache: bool,
+    /// Publish blog posts marked with 'draft: true'.
+    pub include_drafts: bool,
+}
+
+/// Generates the site into a staging dir next to the output dir and swaps it into place
+/// once every task succeeded, a failed build leaves the previous output untouched.
+pub async fn generate_site(options: BuildOptions) -> Result<(), SiteError> {
+    let output_dir = options.paths.output_dir.clone();
+    let staging_dir = options.paths.get_staging_dir();
+    let replaced_dir = options.paths.get_replaced_dir();
+
+    // a publish that failed halfway left the previous output in the replaced dir:
+    if !Helper::exists_dir(&output_dir) && Helper::exists_dir(&replaced_dir) {
+        Helper::rename_dir(&replaced_dir, &output_dir)?;
+    }
+
+    // remove what a failed or interrupted build left behind:
+    if Helper::exists_dir(&staging_dir) {
+        Helper::remove_dir_all(&staging_dir)?;
+    }
+
+    let mut staging_options = options.clone();
+    staging_options.paths.output_dir = staging_dir.clone();
+    let result = match tokio::spawn(generate_staging_dir(staging_options, output_dir)).await {
+        Ok(result) => result,
+        Err(e) => Err(SiteError::from(e)),
+    };
+    let cache = match result {
+        Ok(cache) => cache,
+        Err(e) => {
+            // keep the published output, only drop the half written staging dir (no task
+            // writes to it anymore):
+            if Helper::exists_dir(&staging_dir)
+                && let Err(remove_error) = Helper::remove_dir_all(&staging_dir)
+            {
+                eprintln!("error: {}", remove_error);
+            }
+            return Err(e);
+        }
+    };
+
+    publish_staging_dir(&options.paths)?;
+
+    // remember this build for the next one, only now that its output is published:
+    return cache.save();
+}
+
+async fn generate_staging_dir(
+    options: BuildOptions,
+    published_dir: PathBuf,
+) -> Result<Arc<BuildCache>, SiteError> {
+    let paths = Arc::new(options.paths.clone());
+
+    // make sure the content dirs 'blog-posts' and 'screenshots' exist, templates are
+    // compiled in or part of the theme:
+    // (this tool must be executed in root folder or be given it with '--source')
+    if !Helper::exists_dir(&paths.get_blog_posts_dir())
+        || !Helper::exists_dir(&paths.get_screenshots_dir())
     {
-        panic!(
-            "flow-web must be executed in root folder where 'blog-posts', 'screenshots' and 'templates' folders reside."
-        );
+        return Err(SiteError::content(&format!(
+            "Did not find 'blog-posts' and 'screenshots' folders in '{}', execute flow-web in root folder or give it with '--source'.",
+            paths.source_dir.display()
+        )));
     }
 
-    // remove old output dir if exists:
-    if Helper::exists_dir(&Helper::get_output_dir()) {
-        Helper::remove_dir_all(&Helper::get_output_dir());
+    // read site wide settings:
+    let config = parse_config_file(paths.config_file.clone()).await?;
+
+    // compiled templates or runtime templates of the theme set in 'site.conf':
+    let theme = Arc::new(Theme::load(&paths, &config.theme)?);
+
+    // projects, galleries and pages are read first, as their menu entries are shown on
+    // every page:
+    let mut projects = parse_projects(&paths).await?;
+    let galleries = parse_galleries(&paths).await?;
+    let blog_base_dir = "blog";
+    let mut pages = parse_pages(&paths, blog_base_dir, &projects, &galleries).await?;
+    let menu = Arc::new(Menu::from_sources(
+        &projects,
+        &galleries,
+        &pages,
+        &config.feed_formats,
+    ));
+    for project in &mut projects {
+        project.menu = menu.clone();
+    }
+    for page in &mut pages {
+        page.menu = menu.clone();
     }
 
-    // generate core pages:
-    let core_shared = Arc::new(CoreShared::new());
-    let core_handle = tokio::spawn(generate_core());
+    // hashes of the last build, to only render and write what changed:
+    let cache =
+        Arc::new(BuildCache::load(&paths, &published_dir, &theme, &menu, options.use_cache).await?);
 
-    // generate screenshot pages:
-    let screenshots_shared = Arc::new(ScreenshotsShared::new());
-    let screenshots_handle = tokio::spawn(generate_screenshots(screenshots_shared.clone()));
+    // start from the published output if the cache tells what's in it, so unchanged
+    // pages can be kept (hard links, writes replace files instead of writing through):
+    Helper::create_dir_all(&paths.output_dir)?;
+    if !cache.is_empty() && Helper::exists_dir(&published_dir) {
+        Helper::link_dir_all(&published_dir, &paths.output_dir)?;
+    }
+
+    // collects every written page for the sitemap:
+    let sitemap_shared = Arc::new(SitemapShared::new());
+
+    // everything that may fail is set up before the first task is spawned:
+    let core_shared = Arc::new(CoreShared::new(menu.clone()));
+    let screenshots_shared = Arc::new(ScreenshotsShared::new(galleries, menu.clone()));
+    let blog_shared = Arc::new(BlogShared::new(
+        String::from(blog_base_dir),
+        20,
+        &config,
+        options.include_drafts,
+        menu.clone(),
+    )?);
+
+    // the first featured project is shown with its screenshots on the core index:
+    let featured_project = projects.iter().find(|project| project.featured).cloned();
+    let featured_screenshots = match &featured_project {
+        Some(project) if !project.gallery.is_empty() => screenshots_shared
+            .get_screenshots(6, &project.gallery)
+            .map_err(|e| SiteError::parse(&project.source_path, None, &e.to_string()))?,
+        _ => Vec::new(),
+    };
+
+    let mut tasks = vec![
+        // generate core pages:
+        tokio::spawn(generate_core(
+            paths.clone(),
+            sitemap_shared.clone(),
+            cache.clone(),
+            theme.clone(),
+            pages,
+        )),
+        // generate screenshot pages:
+        tokio::spawn(generate_screenshots(
+            screenshots_shared.clone(),
+            paths.clone(),
+            sitemap_shared.clone(),
+            cache.clone(),
+            theme.clone(),
+        )),
+        // generate project pages:
+        tokio::spawn(generate_projects(
+            projects,
+            screenshots_shared.clone(),
+            menu.clone(),
+            paths.clone(),
+            sitemap_shared.clone(),
+            cache.clone(),
+            theme.clone(),
+        )),
+    ];
 
     // generate blog pages:
-    let blog_base_dir = "blog";
-    let blog_shared = Arc::new(BlogShared::new(String::from(blog_base_dir), 20));
     let blog_handle = tokio::spawn(generate_blog(
         blog_shared.clone(),
         String::from(blog_base_dir),
+        paths.clone(),
+        sitemap_shared.clone(),
+        cache.clone(),
+        theme.clone(),
     ));
 
-    // wait until blog and screenshots are generated:
-    screenshots_handle.await.unwrap();
-    blog_handle.await.unwrap();
+    // wait until blog is generated:
+    let blog_result = Helper::join_all(vec![blog_handle]).await;
 
     // generate core index page now when we've all data we need:
-    core_shared.set_core_index_data(
-        String::from(blog_base_dir),
-        get_latest_blog_posts(blog_shared.clone(), 3),
-        screenshots_shared.get_screenshots(6, "HFGE Screenshots"),
-    );
-    let core_index_handle = tokio::spawn(generate_root_index(core_shared.clone()));
-
-    // wait until core is fully done:
-    core_handle.await.unwrap();
-    core_index_handle.await.unwrap();
+    if blog_result.is_ok() {
+        core_shared.set_core_index_data(
+            String::from(blog_base_dir),
+            get_latest_blog_posts(blog_shared.clone(), 3),
+            featured_project,
+            featured_screenshots,
+        );
+        tasks.push(tokio::spawn(generate_root_index(
+            core_shared.clone(),
+            paths.clone(),
+            sitemap_shared.clone(),
+            cache.clone(),
+            theme.clone(),
+        )));
+    }
+
+    // wait until all tasks are done, also if one of them failed:
+    let tasks_result = Helper::join_all(tasks).await;
+    blog_result?;
+    tasks_result?;
+
+    // generate sitemap and robots.txt when all pages are written:
+    generate_sitemap(sitemap_shared.clone(), paths.clone(), cache.clone()).await?;
+
+    // remove outputs of the last build that weren't generated again:
+    cache.remove_orphans();
+
+    return Ok(cache);
+}
+
+/// Replaces the output dir with the staging dir using two renames, the output dir is
+/// only missing for the moment in between. If the second rename fails the previous output
+/// is moved back, if even that fails the next build moves it back before it starts.
+fn publish_staging_dir(paths: &SitePaths) -> Result<(), SiteError> {
+    let replaced_dir = paths.get_replaced_dir();
+    if Helper::exists_dir(&replaced_dir) {
+        Helper::remove_dir_all(&replaced_dir)?;
+    }
+
+    if Helper::exists_dir(&paths.output_dir) {
+        Helper::rename_dir(&paths.output_dir, &replaced_dir)?;
+    }
+    if let Err(e) = Helper::rename_dir(&paths.get_staging_dir(), &paths.output_dir) {
+        // put the previous output back in place:
+        if Helper::exists_dir(&replaced_dir)
+            && let Err(restore_error) = Helper::rename_dir(&replaced_dir, &paths.output_dir)
+        {
+            eprintln!("error: {}", restore_error);
+        }
+        return Err(e);
+    }
+
+    if Helper::exists_dir(&replaced_dir) {
+        Helper::remove_dir_all(&replaced_dir)?;
+    }
+
+    return Ok(());
 }
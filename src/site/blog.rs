@@ -1,25 +1,52 @@
 // luflow.net web site
 // AGPL-3.0 License (see LICENSE)
 
-use chrono::{Datelike, Local};
+use chrono::{DateTime, Datelike, FixedOffset};
 use sailfish::Template;
 use std::cmp;
-use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use crate::site::blog_post::BlogPost;
 use crate::site::blog_post::parse_markdown_file;
+use crate::site::build_cache::BuildCache;
+use crate::site::config::{Config, SITE_URL, Timezone};
+use crate::site::error::SiteError;
+use crate::site::feed::Feed;
+use crate::site::feed::FeedEntry;
+use crate::site::feed::FeedFormat;
 use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
+use crate::site::search::generate_search;
+use crate::site::sitemap::SitemapShared;
+use crate::site::taxonomy::Taxonomy;
+use crate::site::template::Value;
+use crate::site::theme::{Theme, ThemeTemplate};
+use crate::site::topic::get_topic_name;
+use crate::site::topic::get_topic_with_parents;
+use crate::site::topic::parse_topic_file;
 
 pub struct BlogShared {
     state: Mutex<BlogState>,
 }
 
 impl BlogShared {
-    pub fn new(base_dir: String, num_previews_per_page: usize) -> Self {
-        Self {
-            state: Mutex::new(BlogState::new(base_dir, num_previews_per_page)),
-        }
+    pub fn new(
+        base_dir: String,
+        num_previews_per_page: usize,
+        config: &Config,
+        include_drafts: bool,
+        menu: Arc<Menu>,
+    ) -> Result<Self, SiteError> {
+        Ok(Self {
+            state: Mutex::new(BlogState::new(
+                base_dir,
+                num_previews_per_page,
+                config,
+                include_drafts,
+                menu,
+            )?),
+        })
     }
 }
 
@@ -28,72 +55,540 @@ impl BlogShared {
 pub struct BlogState {
     base_dir: String,
     num_previews_per_page: usize,
+    include_drafts: bool,
     blog_posts: Vec<BlogPost>,
-    topic_blog_indices: HashMap<String, Vec<usize>>,
-    topics: Vec<String>,
-    topics_sanitized: Vec<String>,
-    year_blog_indices: HashMap<String, Vec<usize>>,
-    years: Vec<String>,
+    taxonomies: Vec<Taxonomy>,
+    feed_formats: Vec<FeedFormat>,
+    feed_max_entries: usize,
+    timezone: Timezone,
+    date_format: String,
+    overview_section_url: String,
     overview_page_url: String,
     overview_current_page: usize,
     overview_num_pages: usize,
+    overview_indices: Vec<usize>,
     overview_offset: usize,
     overview_num_posts: usize,
     overview_keywords: String,
     overview_title: String,
-    overview_topic: String,
-    overview_topic_sanitized: String,
-    overview_year: String,
+    overview_description: String,
+    overview_taxonomy: usize,
+    overview_term: String,
+    overview_term_sanitized: String,
     overview_type: i32,
+    menu: Arc<Menu>,
 }
 
 impl BlogState {
-    pub fn new(base_dir: String, num_previews_per_page: usize) -> Self {
-        Self {
-            base_dir: base_dir,
-            num_previews_per_page: num_previews_per_page,
+    pub fn new(
+        base_dir: String,
+        num_previews_per_page: usize,
+        config: &Config,
+        include_drafts: bool,
+        menu: Arc<Menu>,
+    ) -> Result<Self, SiteError> {
+        // topics and years are always available, others are configured in 'site.conf':
+        let mut taxonomies = vec![
+            Taxonomy::new("topic", "Topics", true),
+            Taxonomy::new("year", "Years", false),
+        ];
+        for name in &config.taxonomies {
+            taxonomies.push(Taxonomy::from_config(name));
+        }
+
+        // topics always get feeds, others only when configured in 'site.conf':
+        taxonomies[0].has_feed = true;
+        for name in &config.taxonomy_feeds {
+            match taxonomies
+                .iter_mut()
+                .find(|taxonomy| &taxonomy.name == name)
+            {
+                Some(taxonomy) => taxonomy.has_feed = true,
+                _ => {
+                    return Err(SiteError::content(&format!(
+                        "Did not find taxonomy: '{}' to generate feeds for.",
+                        name
+                    )));
+                }
+            }
+        }
+
+        Ok(Self {
+            base_dir,
+            num_previews_per_page,
+            include_drafts,
             blog_posts: Vec::new(),
-            topic_blog_indices: HashMap::new(),
-            topics: Vec::new(),
-            topics_sanitized: Vec::new(),
-            year_blog_indices: HashMap::new(),
-            years: Vec::new(),
+            taxonomies,
+            feed_formats: config.feed_formats.clone(),
+            feed_max_entries: config.feed_max_entries,
+            timezone: config.timezone,
+            date_format: config.date_format.clone(),
+            overview_section_url: String::new(),
             overview_page_url: String::new(),
             overview_current_page: 0,
             overview_num_pages: 0,
+            overview_indices: Vec::new(),
             overview_offset: 0,
             overview_num_posts: 0,
             overview_keywords: String::new(),
             overview_title: String::new(),
-            overview_topic: String::new(),
-            overview_topic_sanitized: String::new(),
-            overview_year: String::new(),
+            overview_description: String::new(),
+            overview_taxonomy: 0,
+            overview_term: String::new(),
+            overview_term_sanitized: String::new(),
             overview_type: 0,
+            menu,
+        })
+    }
+
+    /// Returns the url of page `page` of the current overview. The first page resolves to
+    /// the section root (e.g. 'blog' instead of 'blog/page/1'), as both have the same
+    /// content.
+    fn get_overview_page_url(&self, page: usize) -> String {
+        if page == 1 {
+            return self.overview_section_url.clone();
+        }
+
+        return format!("{}/page/{}", self.overview_section_url, page);
+    }
+
+    /// Returns the blog posts shown on the current page of the current overview.
+    fn get_overview_posts(&self) -> Vec<&BlogPost> {
+        let end = cmp::min(
+            self.overview_offset + self.overview_num_posts,
+            self.overview_indices.len(),
+        );
+
+        return self.overview_indices[cmp::min(self.overview_offset, end)..end]
+            .iter()
+            .filter_map(|index| self.blog_posts.get(*index))
+            .collect();
+    }
+}
+
+struct BlogIndexEntry {
+    name: String,
+    url: String,
+    num_posts: usize,
+}
+
+#[derive(Template)]
+#[template(path = "blog_index.stpl")]
+struct BlogIndexTemplate {
+    base_dir: String,
+    page_url: String,
+    name: String,
+    title: String,
+    keywords: String,
+    entries: Vec<BlogIndexEntry>,
+    max_num_posts: usize,
+    weighted: bool,
+    menu: Arc<Menu>,
+}
+
+struct BlogArchiveMonth {
+    name: String,
+    url: String,
+    blog_posts: Vec<BlogPost>,
+}
+
+struct BlogArchiveYear {
+    name: String,
+    url: String,
+    months: Vec<BlogArchiveMonth>,
+}
+
+#[derive(Template)]
+#[template(path = "blog_archive.stpl")]
+struct BlogArchiveTemplate {
+    base_dir: String,
+    page_url: String,
+    name: String,
+    num_posts: usize,
+    years: Vec<BlogArchiveYear>,
+    menu: Arc<Menu>,
+}
+
+impl ThemeTemplate for BlogState {
+    fn get_template_name(&self) -> &str {
+        return "blog_overview.html";
+    }
+
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
+
+    fn get_context(&self) -> Value {
+        let current_page = self.overview_current_page;
+        let get_absolute_page_url =
+            |page: usize| Value::from(Helper::get_absolute_url(&self.get_overview_page_url(page)));
+        let get_posts = |indices: &[usize]| {
+            Value::from(
+                indices
+                    .iter()
+                    .filter_map(|index| self.blog_posts.get(*index))
+                    .map(|post| post.get_summary_context())
+                    .collect::<Vec<Value>>(),
+            )
+        };
+
+        let mut context = Value::map(vec![
+            ("base_dir", Value::from(&self.base_dir)),
+            ("keywords", Value::from(&self.overview_keywords)),
+            ("title", Value::from(&self.overview_title)),
+            ("description", Value::from(&self.overview_description)),
+            ("current_page", Value::from(current_page)),
+            ("num_pages", Value::from(self.overview_num_pages)),
+            (
+                "page_url",
+                Value::from(self.get_overview_page_url(current_page)),
+            ),
+            ("canonical_url", get_absolute_page_url(current_page)),
+            ("prev_url", Value::Null),
+            ("next_url", Value::Null),
+            ("taxonomy", Value::Null),
+            ("term", Value::from(&self.overview_term)),
+            ("term_sanitized", Value::from(&self.overview_term_sanitized)),
+            ("term_parents", Value::from(Vec::new())),
+            ("sub_terms", Value::from(Vec::new())),
+            ("topic_info", Value::Null),
+        ]);
+        if current_page > 1 {
+            context.insert("prev_url", get_absolute_page_url(current_page - 1));
+        }
+        if current_page < self.overview_num_pages {
+            context.insert("next_url", get_absolute_page_url(current_page + 1));
+        }
+
+        context.insert(
+            "feed_formats",
+            Value::from(
+                self.feed_formats
+                    .iter()
+                    .map(|format| format.get_context())
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+
+        // sub terms and topic info of the term of a taxonomy (topic, year, ...):
+        if self.overview_type == 1
+            && let Some(taxonomy) = self.taxonomies.get(self.overview_taxonomy)
+        {
+            let get_term_url = |term: &str| {
+                format!(
+                    "{}/{}/{}",
+                    self.base_dir,
+                    taxonomy.name,
+                    Helper::sanitize_path(term)
+                )
+            };
+
+            context.insert(
+                "taxonomy",
+                Value::map(vec![
+                    ("name", Value::from(&taxonomy.name)),
+                    ("title", Value::from(&taxonomy.title)),
+                    ("has_feed", Value::from(taxonomy.has_feed)),
+                ]),
+            );
+            context.insert(
+                "term_parents",
+                Value::from(
+                    get_topic_with_parents(&self.overview_term)
+                        .iter()
+                        .map(|term| {
+                            Value::map(vec![
+                                ("name", Value::from(get_topic_name(term))),
+                                ("url", Value::from(get_term_url(term))),
+                            ])
+                        })
+                        .collect::<Vec<Value>>(),
+                ),
+            );
+            context.insert(
+                "sub_terms",
+                Value::from(
+                    taxonomy
+                        .get_sub_terms(&self.overview_term)
+                        .iter()
+                        .map(|term| {
+                            Value::map(vec![
+                                ("name", Value::from(get_topic_name(term))),
+                                ("url", Value::from(get_term_url(term))),
+                                ("num_posts", Value::from(taxonomy.get_num_blog_posts(term))),
+                            ])
+                        })
+                        .collect::<Vec<Value>>(),
+                ),
+            );
+
+            // topic description and pinned posts are only shown on the first page:
+            if current_page == 1
+                && let Some(topic_info) = taxonomy.infos.get(&self.overview_term_sanitized)
+            {
+                let pinned: Vec<usize> = topic_info
+                    .pinned
+                    .iter()
+                    .filter_map(|url| self.blog_posts.iter().position(|post| &post.url == url))
+                    .collect();
+                context.insert(
+                    "topic_info",
+                    Value::map(vec![
+                        ("image", Value::from(topic_info.get_image_url())),
+                        ("html", Value::from(&topic_info.html)),
+                        ("pinned", get_posts(&pinned)),
+                    ]),
+                );
+            }
         }
+
+        context.insert(
+            "posts",
+            Value::from(
+                self.get_overview_posts()
+                    .iter()
+                    .map(|post| post.get_summary_context())
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+
+        context.insert(
+            "pages",
+            Value::from(
+                (1..self.overview_num_pages + 1)
+                    .map(|page| {
+                        Value::map(vec![
+                            ("number", Value::from(page)),
+                            ("url", Value::from(self.get_overview_page_url(page))),
+                            ("is_current", Value::from(page == current_page)),
+                        ])
+                    })
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+
+        context.insert(
+            "taxonomies",
+            Value::from(
+                self.taxonomies
+                    .iter()
+                    .map(|taxonomy| {
+                        let terms: Vec<Value> = taxonomy
+                            .get_root_terms()
+                            .into_iter()
+                            .map(|(term, term_sanitized)| {
+                                Value::map(vec![
+                                    ("name", Value::from(term)),
+                                    (
+                                        "url",
+                                        Value::from(format!(
+                                            "{}/{}/{}",
+                                            self.base_dir, taxonomy.name, term_sanitized
+                                        )),
+                                    ),
+                                    ("num_posts", Value::from(taxonomy.get_num_blog_posts(term))),
+                                ])
+                            })
+                            .collect();
+
+                        Value::map(vec![
+                            ("name", Value::from(&taxonomy.name)),
+                            ("title", Value::from(&taxonomy.title)),
+                            (
+                                "url",
+                                Value::from(format!("{}/{}", self.base_dir, taxonomy.name)),
+                            ),
+                            ("terms", Value::from(terms)),
+                        ])
+                    })
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+
+        return context;
     }
 }
 
-pub async fn generate_blog(shared: Arc<BlogShared>, base_dir: String) {
-    // create output dirs for topic, year and feeds:
-    create_output_dirs(shared.clone());
+impl ThemeTemplate for BlogIndexTemplate {
+    fn get_template_name(&self) -> &str {
+        return "blog_index.html";
+    }
+
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
+
+    fn get_context(&self) -> Value {
+        let entries: Vec<Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                // scale font size between 0.8em and 1.6em depending on post count:
+                let mut style = String::new();
+                if self.weighted && self.max_num_posts > 0 {
+                    let weight = entry.num_posts as f32 / self.max_num_posts as f32;
+                    style = format!("font-size: {:.2}em;", 0.8 + 0.8 * weight);
+                }
+
+                Value::map(vec![
+                    ("name", Value::from(&entry.name)),
+                    ("url", Value::from(&entry.url)),
+                    ("num_posts", Value::from(entry.num_posts)),
+                    ("style", Value::from(style)),
+                ])
+            })
+            .collect();
+
+        return Value::map(vec![
+            ("base_dir", Value::from(&self.base_dir)),
+            ("page_url", Value::from(&self.page_url)),
+            (
+                "canonical_url",
+                Value::from(Helper::get_absolute_url(&self.page_url)),
+            ),
+            ("name", Value::from(&self.name)),
+            ("title", Value::from(&self.title)),
+            ("keywords", Value::from(&self.keywords)),
+            ("entries", Value::from(entries)),
+        ]);
+    }
+}
+
+impl ThemeTemplate for BlogArchiveTemplate {
+    fn get_template_name(&self) -> &str {
+        return "blog_archive.html";
+    }
+
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
+
+    fn get_context(&self) -> Value {
+        let years: Vec<Value> = self
+            .years
+            .iter()
+            .map(|year| {
+                let months: Vec<Value> = year
+                    .months
+                    .iter()
+                    .map(|month| {
+                        Value::map(vec![
+                            ("name", Value::from(&month.name)),
+                            ("url", Value::from(&month.url)),
+                            (
+                                "posts",
+                                Value::from(
+                                    month
+                                        .blog_posts
+                                        .iter()
+                                        .map(|post| post.get_summary_context())
+                                        .collect::<Vec<Value>>(),
+                                ),
+                            ),
+                        ])
+                    })
+                    .collect();
+
+                Value::map(vec![
+                    ("name", Value::from(&year.name)),
+                    ("url", Value::from(&year.url)),
+                    ("months", Value::from(months)),
+                ])
+            })
+            .collect();
+
+        return Value::map(vec![
+            ("base_dir", Value::from(&self.base_dir)),
+            ("page_url", Value::from(&self.page_url)),
+            (
+                "canonical_url",
+                Value::from(Helper::get_absolute_url(&self.page_url)),
+            ),
+            ("name", Value::from(&self.name)),
+            ("num_posts", Value::from(self.num_posts)),
+            ("years", Value::from(years)),
+        ]);
+    }
+}
+
+pub async fn generate_blog(
+    shared: Arc<BlogShared>,
+    base_dir: String,
+    paths: Arc<SitePaths>,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: Arc<Theme>,
+) -> Result<(), SiteError> {
+    // create output dirs for taxonomies and feeds:
+    create_output_dirs(shared.clone(), &paths)?;
 
     // parse all the markdown files in 'blog-posts' folder:
-    parse_markdown_files(shared.clone(), base_dir).await;
+    parse_markdown_files(shared.clone(), base_dir, &paths).await?;
+
+    // parse optional topic descriptions in 'topics' folder:
+    parse_topic_files(shared.clone(), &paths).await?;
 
     // generate all individual blog posts:
-    generate_blog_posts(shared.clone()).await;
+    generate_blog_posts(
+        shared.clone(),
+        &paths,
+        sitemap.clone(),
+        cache.clone(),
+        &theme,
+    )
+    .await?;
 
     // generate blog overview:
-    generate_overview_posts(shared.clone()).await;
+    generate_overview_posts(
+        shared.clone(),
+        &paths,
+        sitemap.clone(),
+        cache.clone(),
+        &theme,
+    )
+    .await?;
+
+    // generate blog overview and landing page for each taxonomy (topic, year, ...):
+    let num_taxonomies = shared.state.lock().unwrap().taxonomies.len();
+    for i in 0..num_taxonomies {
+        generate_overview_taxonomy(
+            shared.clone(),
+            i,
+            &paths,
+            sitemap.clone(),
+            cache.clone(),
+            &theme,
+        )
+        .await?;
+        generate_index_taxonomy(
+            shared.clone(),
+            i,
+            &paths,
+            sitemap.clone(),
+            cache.clone(),
+            &theme,
+        )
+        .await?;
+    }
 
-    // generate blog overview by topic:
-    generate_overview_topic(shared.clone()).await;
+    // generate archive listing all blog posts on a single page:
+    generate_archive(
+        shared.clone(),
+        &paths,
+        sitemap.clone(),
+        cache.clone(),
+        &theme,
+    )
+    .await?;
 
-    // generate blog overview by year:
-    generate_overview_year(shared.clone()).await;
+    // generate search page and its index:
+    {
+        let lock = shared.state.lock().unwrap();
+        generate_search(&lock.blog_posts, lock.menu.clone(), &paths, &cache, &theme)?;
+    }
 
-    // generate blog atom feed:
-    generate_atom_feed(shared.clone()).await;
+    // generate blog feeds (atom, rss and/or json):
+    return generate_feeds(shared.clone(), &paths, cache.clone()).await;
 }
 
 pub fn get_latest_blog_posts(shared: Arc<BlogShared>, num_posts: usize) -> Vec<BlogPost> {
@@ -109,95 +604,161 @@ pub fn get_latest_blog_posts(shared: Arc<BlogShared>, num_posts: usize) -> Vec<B
     return blog_posts;
 }
 
-fn create_output_dirs(shared: Arc<BlogShared>) {
+fn create_output_dirs(shared: Arc<BlogShared>, paths: &SitePaths) -> Result<(), SiteError> {
     let lock = shared.state.lock().unwrap();
 
-    Helper::create_dir_all(&Helper::get_output_dir().join(&lock.base_dir).join("topic"));
-    Helper::create_dir_all(&Helper::get_output_dir().join(&lock.base_dir).join("year"));
-    Helper::create_dir_all(&Helper::get_output_dir().join("feeds"));
+    for taxonomy in &lock.taxonomies {
+        Helper::create_dir_all(&paths.output_dir.join(&lock.base_dir).join(&taxonomy.name))?;
+        if taxonomy.has_feed {
+            Helper::create_dir_all(&paths.output_dir.join("feeds").join(&taxonomy.name))?;
+        }
+    }
+    Helper::create_dir_all(&paths.output_dir.join(&lock.base_dir).join("archive"))?;
+    Helper::create_dir_all(&paths.output_dir.join("feeds"))?;
+
+    Ok(())
 }
 
-async fn parse_markdown_files(shared: Arc<BlogShared>, base_dir: String) {
-    let mut reader = tokio::fs::read_dir("blog-posts").await.unwrap();
+async fn parse_markdown_files(
+    shared: Arc<BlogShared>,
+    base_dir: String,
+    paths: &SitePaths,
+) -> Result<(), SiteError> {
+    // front matter keys of configured taxonomies:
+    let taxonomy_names: Vec<String> = shared
+        .state
+        .lock()
+        .unwrap()
+        .taxonomies
+        .iter()
+        .map(|taxonomy| taxonomy.name.clone())
+        .collect();
+    let timezone = shared.state.lock().unwrap().timezone;
+    let date_format = shared.state.lock().unwrap().date_format.clone();
+    let include_drafts = shared.state.lock().unwrap().include_drafts;
+    let menu = shared.state.lock().unwrap().menu.clone();
+
     let mut tasks = vec![];
-    loop {
-        if let Some(f) = reader.next_entry().await.unwrap() {
-            tasks.push(tokio::spawn(parse_markdown_file(
-                f.path(),
-                base_dir.clone(),
-            )));
-        } else {
-            break;
-        }
+    for path in Helper::get_files_recursive(&paths.get_blog_posts_dir())? {
+        tasks.push(tokio::spawn(parse_markdown_file(
+            path,
+            base_dir.clone(),
+            taxonomy_names.clone(),
+            timezone,
+            date_format.clone(),
+            menu.clone(),
+        )));
     }
 
     // await all created blog_posts:
     let mut blog_posts = Vec::with_capacity(tasks.len());
-    for task in tasks {
-        blog_posts.push(task.await.unwrap());
+    for blog_post in Helper::join_all(tasks).await? {
+        // drafts are only published when asked for on the command line:
+        if !blog_post.draft || include_drafts {
+            blog_posts.push(blog_post);
+        }
     }
 
     // sort so that latest is first:
     blog_posts.sort_by(|a, b| b.get_published_date().cmp(a.get_published_date()));
 
-    let mut topic_blog_indices: HashMap<String, Vec<usize>> = HashMap::new();
-    let mut year_blog_indices: HashMap<String, Vec<usize>> = HashMap::new();
-    for (i, post) in blog_posts.iter().enumerate() {
-        // add blog_id for each blog topic for later lookup:
-        for topic in post.get_topics() {
-            if let Some(indices) = topic_blog_indices.get_mut(topic) {
-                indices.push(i);
-            } else {
-                topic_blog_indices.insert(String::from(topic), vec![i]);
-            }
-        }
+    // move into state:
+    let mut lock = shared.state.lock().unwrap();
 
-        // add blog_id for each blog year for later lookup:
-        let year_str = post.get_year();
-        if let Some(indices) = year_blog_indices.get_mut(&year_str) {
-            indices.push(i);
-        } else {
-            year_blog_indices.insert(String::from(year_str), vec![i]);
+    // add blog_id for each taxonomy term for later lookup:
+    for taxonomy in &mut lock.taxonomies {
+        for (i, post) in blog_posts.iter().enumerate() {
+            taxonomy.add_blog_post(i, &post.get_taxonomy_terms(&taxonomy.name));
         }
+        taxonomy.sort_terms()?;
     }
 
-    // move into state:
-    let mut lock = shared.state.lock().unwrap();
     lock.blog_posts = blog_posts;
 
-    for (key, _value) in &topic_blog_indices {
-        lock.topics.push(String::from(key));
-        lock.topics_sanitized
-            .push(String::from(Helper::sanitize_string(key)));
+    Ok(())
+}
+
+async fn parse_topic_files(shared: Arc<BlogShared>, paths: &SitePaths) -> Result<(), SiteError> {
+    let topics_dir = paths.get_topics_dir();
+    if !Helper::exists_dir(&topics_dir) {
+        return Ok(());
     }
-    // sort by topic name:
-    lock.topics
-        .sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
-    lock.topics_sanitized.sort_by(|a, b| a.cmp(b));
 
-    for (key, _value) in &year_blog_indices {
-        lock.years.push(String::from(key));
+    let mut tasks = vec![];
+    for path in Helper::get_files_recursive(&topics_dir)? {
+        tasks.push(tokio::spawn(parse_topic_file(path, topics_dir.clone())));
     }
-    // sort by year:
-    lock.years.sort_by(|a, b| a.cmp(b));
 
-    // move created hashmaps:
-    lock.topic_blog_indices = topic_blog_indices;
-    lock.year_blog_indices = year_blog_indices;
+    // await all created topic infos:
+    let topic_infos = Helper::join_all(tasks).await?;
+
+    let mut lock = shared.state.lock().unwrap();
+    for topic_info in topic_infos {
+        // make sure all pinned blog posts exist:
+        for url in &topic_info.pinned {
+            if !lock.blog_posts.iter().any(|post| &post.url == url) {
+                return Err(SiteError::content(&format!(
+                    "Did not find pinned blog post: '{}' for topic: '{}'.",
+                    url, topic_info.slug
+                )));
+            }
+        }
+
+        let taxonomy = match lock.taxonomies.iter_mut().find(|t| t.name == "topic") {
+            Some(value) => value,
+            _ => return Err(SiteError::content("Did not find taxonomy: 'topic'.")),
+        };
+        if !taxonomy.terms_sanitized.contains(&topic_info.slug) {
+            return Err(SiteError::content(&format!(
+                "Found topic description for unknown topic: '{}'.",
+                topic_info.slug
+            )));
+        }
+
+        taxonomy.infos.insert(topic_info.slug.clone(), topic_info);
+    }
+
+    Ok(())
 }
 
-async fn generate_blog_posts(shared: Arc<BlogShared>) {
+async fn generate_blog_posts(
+    shared: Arc<BlogShared>,
+    paths: &SitePaths,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: &Theme,
+) -> Result<(), SiteError> {
     let lock = shared.state.lock().unwrap();
 
     for post in &lock.blog_posts {
-        post.create_output_dir();
-        post.generate();
+        // only render posts whose markdown file changed since the last build:
+        let source = std::fs::read(&post.source_path)
+            .map_err(|e| SiteError::io("read", &post.source_path, e))?;
+        if cache.is_source_changed(&post.source_path, &source)
+            || !cache.keep_file(&post.get_output_file(paths))
+        {
+            post.create_output_dir(paths)?;
+            post.generate(paths, &cache, theme)?;
+        }
+        sitemap.add_page(
+            &format!("{}/{}", post.base_dir, post.url),
+            Some(post.updated),
+        );
     }
+
+    Ok(())
 }
 
-async fn generate_overview_posts(shared: Arc<BlogShared>) {
+async fn generate_overview_posts(
+    shared: Arc<BlogShared>,
+    paths: &SitePaths,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: &Theme,
+) -> Result<(), SiteError> {
     let mut lock = shared.state.lock().unwrap();
 
+    lock.overview_indices = (0..lock.blog_posts.len()).collect();
     lock.overview_offset = 0;
     let num = lock.blog_posts.len() as f32 / lock.num_previews_per_page as f32;
     lock.overview_num_pages = num.ceil() as usize;
@@ -205,10 +766,12 @@ async fn generate_overview_posts(shared: Arc<BlogShared>) {
     lock.overview_keywords = String::from("overview");
     lock.overview_title = String::from("Blog Overview");
     lock.overview_type = 0;
+    lock.overview_section_url = lock.base_dir.clone();
 
     for i in 1..lock.overview_num_pages + 1 {
         lock.overview_current_page = i;
         lock.overview_page_url = format!("{}/page/{}", lock.base_dir, i);
+        lock.overview_description = format!("{} Page {}", lock.overview_title, i);
 
         if i == lock.overview_num_pages {
             lock.overview_num_posts = cmp::min(
@@ -219,54 +782,98 @@ async fn generate_overview_posts(shared: Arc<BlogShared>) {
 
         if lock.overview_current_page == 1 {
             // write page to disk:
-            Helper::write_file_sync(
-                &Helper::get_output_dir()
-                    .join(&lock.base_dir)
-                    .join("index.html"),
-                &lock.render().unwrap().as_bytes(),
-            )
-            .unwrap();
+            let output_file = paths.output_dir.join(&lock.base_dir).join("index.html");
+            let html = theme.render(&*lock, &output_file)?;
+            cache.write_file_sync(&output_file, html.as_bytes())?;
         }
 
+        // first page is listed by its section root, e.g. 'blog/' instead of 'blog/page/1/':
+        let indices: Vec<usize> =
+            (lock.overview_offset..lock.overview_offset + lock.overview_num_posts).collect();
+        sitemap.add_page(
+            &lock.get_overview_page_url(i),
+            get_last_updated(&lock, &indices),
+        );
+
         // create dir recursively:
-        Helper::create_dir_all(&Helper::get_output_dir().join(&lock.overview_page_url));
+        Helper::create_dir_all(&paths.output_dir.join(&lock.overview_page_url))?;
 
         // write page to disk:
-        Helper::write_file_sync(
-            &Helper::get_output_dir()
-                .join(&lock.overview_page_url)
-                .join("index.html"),
-            &lock.render().unwrap().as_bytes(),
-        )
-        .unwrap();
+        let output_file = paths
+            .output_dir
+            .join(&lock.overview_page_url)
+            .join("index.html");
+        let html = theme.render(&*lock, &output_file)?;
+        cache.write_file_sync(&output_file, html.as_bytes())?;
 
         lock.overview_offset += lock.overview_num_posts;
     }
+
+    Ok(())
 }
 
-async fn generate_overview_topic(shared: Arc<BlogShared>) {
+async fn generate_overview_taxonomy(
+    shared: Arc<BlogShared>,
+    taxonomy_index: usize,
+    paths: &SitePaths,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: &Theme,
+) -> Result<(), SiteError> {
     let mut lock = shared.state.lock().unwrap();
 
     // TODO: How to solve this without a clone??
-    for (key, indices) in &lock.topic_blog_indices.clone() {
+    let taxonomy = lock.taxonomies[taxonomy_index].clone();
+    lock.overview_taxonomy = taxonomy_index;
+    lock.overview_type = 1;
+
+    for (key, key_sanitized) in taxonomy.terms.iter().zip(&taxonomy.terms_sanitized) {
+        let indices = match taxonomy.blog_indices.get(key) {
+            Some(value) => value,
+            _ => {
+                return Err(SiteError::content(&format!(
+                    "Did not find blog posts for {}: '{}'.",
+                    taxonomy.name, key
+                )));
+            }
+        };
+        if let Some(index) = indices
+            .iter()
+            .find(|index| **index >= lock.blog_posts.len())
+        {
+            return Err(SiteError::content(&format!(
+                "Did not find blog post for index: {}.",
+                index
+            )));
+        }
+        let term_url = format!("{}/{}/{}", lock.base_dir, taxonomy.name, key_sanitized);
+
+        lock.overview_section_url = term_url.clone();
+        lock.overview_indices = indices.clone();
         lock.overview_offset = 0;
         let num = indices.len() as f32 / lock.num_previews_per_page as f32;
         lock.overview_num_pages = num.ceil() as usize;
         lock.overview_num_posts = lock.num_previews_per_page;
-        lock.overview_keywords = format!("topic, {}", key);
-        lock.overview_topic = String::from(key);
-        lock.overview_topic_sanitized = Helper::sanitize_string(key);
-        lock.overview_type = 1;
+        lock.overview_keywords = format!("{}, {}", taxonomy.name, key);
+        lock.overview_term = String::from(key);
+        lock.overview_term_sanitized = String::from(key_sanitized);
+        lock.overview_title = format!(
+            "Blog posts by {}: {}",
+            taxonomy.name,
+            taxonomy.get_term_title(key)
+        );
 
         for i in 1..lock.overview_num_pages + 1 {
             lock.overview_current_page = i;
-            lock.overview_page_url = format!(
-                "{}/topic/{}/page/{}",
-                lock.base_dir,
-                Helper::sanitize_string(key),
-                i
-            );
-            lock.overview_title = format!("Blog posts by topic: {}", key);
+            lock.overview_page_url = format!("{}/page/{}", term_url, i);
+
+            // use term description (if any) as meta description:
+            lock.overview_description = format!("{} Page {}", lock.overview_title, i);
+            if let Some(info) = taxonomy.infos.get(key_sanitized)
+                && !info.description.is_empty()
+            {
+                lock.overview_description = info.description.clone();
+            }
 
             if i == lock.overview_num_pages {
                 lock.overview_num_posts = cmp::min(
@@ -277,220 +884,266 @@ async fn generate_overview_topic(shared: Arc<BlogShared>) {
 
             if lock.overview_current_page == 1 {
                 // create dir recursively:
-                Helper::create_dir_all(
-                    &Helper::get_output_dir()
-                        .join(&lock.base_dir)
-                        .join("topic")
-                        .join(Helper::sanitize_string(key)),
-                );
+                Helper::create_dir_all(&paths.output_dir.join(&term_url))?;
 
                 // write page to disk:
-                Helper::write_file_sync(
-                    &Helper::get_output_dir()
-                        .join(&lock.base_dir)
-                        .join("topic")
-                        .join(Helper::sanitize_string(key))
-                        .join("index.html"),
-                    &lock.render().unwrap().as_bytes(),
-                )
-                .unwrap();
+                let output_file = paths.output_dir.join(&term_url).join("index.html");
+                let html = theme.render(&*lock, &output_file)?;
+                cache.write_file_sync(&output_file, html.as_bytes())?;
             }
 
+            let lastmod = get_last_updated(
+                &lock,
+                &indices[lock.overview_offset..lock.overview_offset + lock.overview_num_posts],
+            );
+            sitemap.add_page(&lock.get_overview_page_url(i), lastmod);
+
             // create dir recursively:
-            Helper::create_dir_all(&Helper::get_output_dir().join(&lock.overview_page_url));
+            Helper::create_dir_all(&paths.output_dir.join(&lock.overview_page_url))?;
 
             // write page to disk:
-            Helper::write_file_sync(
-                &Helper::get_output_dir()
-                    .join(&lock.overview_page_url)
-                    .join("index.html"),
-                &lock.render().unwrap().as_bytes(),
-            )
-            .unwrap();
+            let output_file = paths
+                .output_dir
+                .join(&lock.overview_page_url)
+                .join("index.html");
+            let html = theme.render(&*lock, &output_file)?;
+            cache.write_file_sync(&output_file, html.as_bytes())?;
 
             lock.overview_offset += lock.overview_num_posts;
         }
     }
-}
-
-async fn generate_overview_year(shared: Arc<BlogShared>) {
-    let mut lock = shared.state.lock().unwrap();
 
-    // TODO: How to solve this without a clone??
-    for (key, indices) in &lock.year_blog_indices.clone() {
-        lock.overview_offset = 0;
-        let num = indices.len() as f32 / lock.num_previews_per_page as f32;
-        lock.overview_num_pages = num.ceil() as usize;
-        lock.overview_num_posts = lock.num_previews_per_page;
-        lock.overview_keywords = format!("year, {}", key);
-        lock.overview_year = String::from(key);
-        lock.overview_type = 2;
-
-        for i in 1..lock.overview_num_pages + 1 {
-            lock.overview_current_page = i;
-            lock.overview_page_url = format!(
-                "{}/year/{}/page/{}",
-                lock.base_dir,
-                Helper::sanitize_string(key),
-                i
-            );
-            lock.overview_title = format!("Blog posts by year: {}", key);
-
-            if i == lock.overview_num_pages {
-                lock.overview_num_posts = cmp::min(
-                    indices.len() - lock.num_previews_per_page * (i - 1),
-                    lock.num_previews_per_page,
-                );
-            }
+    Ok(())
+}
 
-            if lock.overview_current_page == 1 {
-                // create dir recursively:
-                Helper::create_dir_all(
-                    &Helper::get_output_dir()
-                        .join(&lock.base_dir)
-                        .join("year")
-                        .join(Helper::sanitize_string(key)),
-                );
+async fn generate_index_taxonomy(
+    shared: Arc<BlogShared>,
+    taxonomy_index: usize,
+    paths: &SitePaths,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: &Theme,
+) -> Result<(), SiteError> {
+    let lock = shared.state.lock().unwrap();
+    let taxonomy = &lock.taxonomies[taxonomy_index];
+
+    // only root terms, sub terms (e.g. months of a year) are listed on their parent's page:
+    let root_terms = taxonomy.get_root_terms();
+    let mut entries = Vec::with_capacity(root_terms.len());
+    for (term, term_sanitized) in root_terms {
+        entries.push(BlogIndexEntry {
+            name: String::from(term),
+            url: format!("{}/{}/{}", lock.base_dir, taxonomy.name, term_sanitized),
+            num_posts: taxonomy.get_num_blog_posts(term),
+        });
+    }
 
-                // write page to disk:
-                Helper::write_file_sync(
-                    &Helper::get_output_dir()
-                        .join(&lock.base_dir)
-                        .join("year")
-                        .join(Helper::sanitize_string(key))
-                        .join("index.html"),
-                    &lock.render().unwrap().as_bytes(),
-                )
-                .unwrap();
-            }
+    let taxonomy_indices: Vec<usize> = taxonomy.blog_indices.values().flatten().copied().collect();
+    let lastmod = get_last_updated(&lock, &taxonomy_indices);
+
+    // weighted taxonomies (e.g. topics) are shown as a tag cloud, others as a plain list:
+    let ctx = BlogIndexTemplate {
+        base_dir: lock.base_dir.clone(),
+        page_url: format!("{}/{}", lock.base_dir, taxonomy.name),
+        name: taxonomy.title.clone(),
+        title: format!("Blog posts by {}", taxonomy.name),
+        keywords: taxonomy.title.to_lowercase(),
+        max_num_posts: entries.iter().map(|e| e.num_posts).max().unwrap_or(0),
+        entries,
+        weighted: taxonomy.weighted,
+        menu: lock.menu.clone(),
+    };
+
+    // write page to disk:
+    let output_file = paths.output_dir.join(&ctx.page_url).join("index.html");
+    let html = theme.render(&ctx, &output_file)?;
+    cache.write_file_sync(&output_file, html.as_bytes())?;
+    sitemap.add_page(&ctx.page_url, lastmod);
+
+    Ok(())
+}
 
-            // create dir recursively:
-            Helper::create_dir_all(&Helper::get_output_dir().join(&lock.overview_page_url));
+async fn generate_archive(
+    shared: Arc<BlogShared>,
+    paths: &SitePaths,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: &Theme,
+) -> Result<(), SiteError> {
+    let lock = shared.state.lock().unwrap();
 
-            // write page to disk:
-            Helper::write_file_sync(
-                &Helper::get_output_dir()
-                    .join(&lock.overview_page_url)
-                    .join("index.html"),
-                &lock.render().unwrap().as_bytes(),
-            )
-            .unwrap();
+    // blog posts are sorted latest first, so group them by year and month in that order:
+    let mut years: Vec<BlogArchiveYear> = Vec::new();
+    for post in &lock.blog_posts {
+        let year = post.get_year();
+        if years.last().map(|y| &y.name) != Some(&year) {
+            years.push(BlogArchiveYear {
+                url: format!("{}/year/{}", lock.base_dir, year),
+                name: year,
+                months: Vec::new(),
+            });
+        }
 
-            lock.overview_offset += lock.overview_num_posts;
+        let month = post.published.format("%B").to_string();
+        let archive_year = years.last_mut().unwrap();
+        if archive_year.months.last().map(|m| &m.name) != Some(&month) {
+            archive_year.months.push(BlogArchiveMonth {
+                name: month,
+                url: format!("{}/{:02}", archive_year.url, post.published.month()),
+                blog_posts: Vec::new(),
+            });
         }
+        archive_year
+            .months
+            .last_mut()
+            .unwrap()
+            .blog_posts
+            .push(post.clone());
     }
+
+    let ctx = BlogArchiveTemplate {
+        base_dir: lock.base_dir.clone(),
+        page_url: format!("{}/archive", lock.base_dir),
+        name: String::from("Archive"),
+        num_posts: lock.blog_posts.len(),
+        years,
+        menu: lock.menu.clone(),
+    };
+
+    // write page to disk:
+    let output_file = paths.output_dir.join(&ctx.page_url).join("index.html");
+    let html = theme.render(&ctx, &output_file)?;
+    cache.write_file_sync(&output_file, html.as_bytes())?;
+    let all_indices: Vec<usize> = (0..lock.blog_posts.len()).collect();
+    sitemap.add_page(&ctx.page_url, get_last_updated(&lock, &all_indices));
+
+    Ok(())
 }
 
-async fn generate_atom_feed(shared: Arc<BlogShared>) {
+async fn generate_feeds(
+    shared: Arc<BlogShared>,
+    paths: &SitePaths,
+    cache: Arc<BuildCache>,
+) -> Result<(), SiteError> {
     let lock = shared.state.lock().unwrap();
 
-    let mut _feed_data = String::new();
-    let date_now = get_date_now_for_feed();
-
-    // header info:
-    _feed_data = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
-    _feed_data += "<feed xmlns=\"http://www.w3.org/2005/Atom\">\n";
-    _feed_data += "    <id>https:/www.luflow.net/feeds/blog.atom</id>\n";
-    _feed_data += "    <title>luflow.net Blog</title>\n";
-    _feed_data += "    <updated>";
-    _feed_data += &date_now;
-    _feed_data += "</updated>\n";
-    _feed_data += "    <generator>https://codeberg.org/hfsoulz/flow-web.git</generator>\n";
-    _feed_data += "    <author>\n";
-    _feed_data += "        <name>luflow.net</name>\n";
-    _feed_data += "        <uri>https://www.luflow.net/</uri>\n";
-    _feed_data += "    </author>\n";
-    _feed_data += "    <link rel=\"alternate\" href=\"https:/www.luflow.net/blog/\"/>\n";
-    _feed_data += "    <link rel=\"self\" href=\"https:/www.luflow.net/feeds/blog.atom\"/>\n";
-    _feed_data += "    <subtitle>This blog is dedicated to free software in general.</subtitle>\n";
-    _feed_data += "    <logo>https:/www.luflow.net/static/img/icon.png</logo>\n";
-    _feed_data += "    <icon>https:/www.luflow.net/favicon.ico</icon>\n";
-
-    // each blog entry:
-    for blog_post in &lock.blog_posts {
-        _feed_data += "    <entry>\n";
-
-        // author:
-        _feed_data += "        <author>\n";
-        _feed_data += "            <name>Andreas</name>\n";
-        _feed_data += "        </author>\n";
-
-        // title:
-        _feed_data += "        <title type=\"html\"><![CDATA[";
-        _feed_data += &blog_post.title;
-        _feed_data += "]]></title>\n";
-
-        // link href:
-        _feed_data += "        <link href=\"https:/www.luflow.net/";
-        _feed_data += &lock.base_dir;
-        _feed_data += "/";
-        _feed_data += &blog_post.url;
-        _feed_data += "/\"/>\n";
-
-        // id:
-        _feed_data += "        <id>https:/www.luflow.net/";
-        _feed_data += &lock.base_dir;
-        _feed_data += "/";
-        _feed_data += &blog_post.url;
-        _feed_data += "/</id>\n";
-
-        // updated:
-        _feed_data += "        <updated>";
-        _feed_data += &blog_post.updated_for_feed;
-        _feed_data += "</updated>\n";
-
-        // published:
-        _feed_data += "        <published>";
-        _feed_data += &blog_post.published_for_feed;
-        _feed_data += "</published>\n";
-
-        // categories:
-        for topic in &blog_post.topics {
-            _feed_data += "        <category term=\"";
-            _feed_data += &topic;
-            _feed_data += "\"/>\n";
+    // main feed with all blog posts:
+    let blog_indices: Vec<usize> = (0..lock.blog_posts.len()).collect();
+    let feed = build_feed(
+        &lock,
+        "feeds/blog",
+        "luflow.net Blog",
+        &format!("{}/", lock.base_dir),
+        &blog_indices,
+    )?;
+    write_feed(&lock, feed, "feeds/blog", paths, &cache)?;
+
+    // one feed for each term of taxonomies with feeds enabled (e.g. topics):
+    for taxonomy in &lock.taxonomies {
+        if !taxonomy.has_feed {
+            continue;
         }
 
-        // summary:
-        _feed_data += "        <summary type=\"html\"><![CDATA[";
-        _feed_data += &blog_post.snippet;
-        _feed_data += "]]></summary>\n";
+        for (term, term_sanitized) in taxonomy.terms.iter().zip(&taxonomy.terms_sanitized) {
+            let feed_name = format!("feeds/{}/{}", taxonomy.name, term_sanitized);
 
-        // content:
-        _feed_data += "        <content type=\"html\"><![CDATA[";
-        _feed_data += &blog_post.html;
-        _feed_data += "]]></content>\n";
+            // create dir recursively (sub terms reside in sub dirs):
+            if let Some(feed_dir) = paths.output_dir.join(&feed_name).parent() {
+                Helper::create_dir_all(&feed_dir.to_path_buf())?;
+            }
 
-        _feed_data += "    </entry>\n";
+            let feed = build_feed(
+                &lock,
+                &feed_name,
+                &format!("luflow.net Blog - {}: {}", taxonomy.name, term),
+                &format!("{}/{}/{}/", lock.base_dir, taxonomy.name, term_sanitized),
+                &taxonomy.blog_indices[term],
+            )?;
+            write_feed(&lock, feed, &feed_name, paths, &cache)?;
+        }
     }
 
-    // close feed:
-    _feed_data += "</feed>";
-
-    // write it out:
-    Helper::write_file_sync(
-        &Helper::get_output_dir().join("feeds").join("blog.atom"),
-        _feed_data.as_bytes(),
-    )
-    .unwrap();
+    Ok(())
 }
 
-fn get_date_now_for_feed() -> String {
-    let date = Local::now();
-
-    let month = date.month();
-    let day = date.day();
-
-    let mut month_str = month.to_string();
-    let mut day_str = day.to_string();
+/// Returns the newest `updated` date of the blog posts in `blog_indices`.
+fn get_last_updated(state: &BlogState, blog_indices: &[usize]) -> Option<DateTime<FixedOffset>> {
+    return blog_indices
+        .iter()
+        .filter_map(|i| state.blog_posts.get(*i))
+        .map(|post| post.updated)
+        .max();
+}
 
-    if month < 10 {
-        month_str = format!("0{}", month);
+/// Builds a feed with the blog posts in `blog_indices`, `feed_name` is the path of the
+/// feed relative to the output dir without extension.
+fn build_feed(
+    state: &BlogState,
+    feed_name: &str,
+    title: &str,
+    alternate_url: &str,
+    blog_indices: &[usize],
+) -> Result<Feed, SiteError> {
+    let mut feed = Feed::new();
+    feed.title = String::from(title);
+    feed.subtitle = String::from("This blog is dedicated to free software in general.");
+    feed.author = String::from("luflow.net");
+    feed.site_url = format!("{}/", SITE_URL);
+    feed.alternate_url = format!("{}/{}", SITE_URL, alternate_url);
+    feed.feed_url = format!("{}/{}", SITE_URL, feed_name);
+    feed.logo_url = format!("{}/static/img/icon.png", SITE_URL);
+    feed.icon_url = format!("{}/favicon.ico", SITE_URL);
+    feed.generator = String::from("https://codeberg.org/hfsoulz/flow-web.git");
+
+    for index in blog_indices {
+        let blog_post = match state.blog_posts.get(*index) {
+            Some(value) => value,
+            _ => {
+                return Err(SiteError::content(&format!(
+                    "Did not find blog post for index: {}.",
+                    index
+                )));
+            }
+        };
+
+        feed.entries.push(FeedEntry {
+            url: format!("{}/{}/{}/", SITE_URL, state.base_dir, blog_post.url),
+            title: blog_post.title.clone(),
+            author: blog_post.author.clone(),
+            published: blog_post.published,
+            updated: blog_post.updated,
+            categories: blog_post.topics.clone(),
+            summary: blog_post.snippet.clone(),
+            content: blog_post.html.clone(),
+        });
     }
 
-    if day < 10 {
-        day_str = format!("0{}", day);
+    return Ok(feed);
+}
+
+/// Writes `feed` in every format enabled in 'site.conf' next to each other, e.g.
+/// 'feeds/blog.atom' and 'feeds/blog.rss', together with its paged archives if it has
+/// more than `feed_max_entries` entries.
+fn write_feed(
+    state: &BlogState,
+    feed: Feed,
+    feed_name: &str,
+    paths: &SitePaths,
+    cache: &BuildCache,
+) -> Result<(), SiteError> {
+    for format in &state.feed_formats {
+        for (suffix, page) in feed.get_pages(state.feed_max_entries, format.get_extension()) {
+            cache.write_file_sync(
+                &paths.output_dir.join(format!(
+                    "{}{}.{}",
+                    feed_name,
+                    suffix,
+                    format.get_extension()
+                )),
+                page.serialize(*format)?.as_bytes(),
+            )?;
+        }
     }
 
-    return format!("{}-{}-{}T{}Z", date.year(), month_str, day_str, date.time());
+    Ok(())
 }
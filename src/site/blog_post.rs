@@ -1,53 +1,71 @@
 // luflow.net web site
 // AGPL-3.0 License (see LICENSE)
 
-use chrono::{Datelike, NaiveDateTime};
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDateTime};
+use regex::Regex;
 use sailfish::Template;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use crate::site::build_cache::BuildCache;
+use crate::site::config::{SITE_URL, Timezone};
+use crate::site::error::SiteError;
+use crate::site::feed::get_json_string;
 use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
+use crate::site::template::Value;
+use crate::site::theme::{Theme, ThemeTemplate};
+use crate::site::topic::normalize_topic;
 
 #[derive(Template)]
 #[template(path = "blog_post.stpl")]
 #[derive(Clone, Debug)]
 pub struct BlogPost {
     pub base_dir: String,
+    pub source_path: PathBuf,
 
     pub author: String,
-    pub published: NaiveDateTime,
-    pub published_for_feed: String,
-    pub updated: NaiveDateTime,
-    pub updated_for_feed: String,
+    pub published: DateTime<FixedOffset>,
+    pub updated: DateTime<FixedOffset>,
     pub topics_comma_separated: String,
     pub topics: Vec<String>,
     pub topics_sanitized: Vec<String>,
     pub topic_base_dir: String,
+    pub taxonomies: HashMap<String, Vec<String>>,
     pub title: String,
     pub snippet: String,
+    pub cover: String,
+    pub draft: bool,
     pub html: String,
     pub url: String,
+    pub date_format: String,
+    pub menu: Arc<Menu>,
 }
 
 impl BlogPost {
     pub fn new(base_dir: String) -> BlogPost {
         BlogPost {
-            base_dir: base_dir,
+            base_dir,
+            source_path: PathBuf::new(),
 
             author: String::new(),
-            published: NaiveDateTime::parse_from_str("2000-01-01 23:56:04", "%Y-%m-%d %H:%M:%S")
-                .unwrap(),
-            published_for_feed: String::new(),
-            updated: NaiveDateTime::parse_from_str("2000-01-01 23:56:04", "%Y-%m-%d %H:%M:%S")
-                .unwrap(),
-            updated_for_feed: String::new(),
+            published: DateTime::parse_from_rfc3339("2000-01-01T23:56:04+00:00").unwrap(),
+            updated: DateTime::parse_from_rfc3339("2000-01-01T23:56:04+00:00").unwrap(),
             topics_comma_separated: String::new(),
             topics: Vec::new(),
             topics_sanitized: Vec::new(),
             topic_base_dir: String::from("topic"),
+            taxonomies: HashMap::new(),
             title: String::new(),
             snippet: String::new(),
+            cover: String::new(),
+            draft: false,
             html: String::new(),
             url: String::new(),
+            date_format: String::new(),
+            menu: Arc::new(Menu::new()),
         }
     }
 
@@ -59,126 +77,313 @@ impl BlogPost {
         return self.published.year().to_string();
     }
 
-    pub fn get_published_date(&self) -> &NaiveDateTime {
+    /// Returns the terms of this post for taxonomy `name` (e.g. "topic", "year" or any
+    /// taxonomy configured in 'site.conf').
+    pub fn get_taxonomy_terms(&self, name: &str) -> Vec<String> {
+        if name == "topic" {
+            return self.get_topics().clone();
+        } else if name == "year" {
+            // months are sub terms of years, e.g. "2022/01":
+            return vec![format!("{}/{:02}", self.get_year(), self.published.month())];
+        }
+
+        return match self.taxonomies.get(name) {
+            Some(terms) => terms.clone(),
+            _ => Vec::new(),
+        };
+    }
+
+    pub fn get_published_date(&self) -> &DateTime<FixedOffset> {
         return &self.published;
     }
 
-    fn get_date_for_feed(&self, date: &NaiveDateTime) -> String {
-        let month = date.month();
-        let day = date.day();
+    pub fn is_updated(&self) -> bool {
+        return self.updated != self.published;
+    }
 
-        let mut month_str = month.to_string();
-        let mut day_str = day.to_string();
+    /// Absolute url of this post, as shared in social media and search engine metadata.
+    pub fn get_absolute_url(&self) -> String {
+        return Helper::get_absolute_url(&format!("{}/{}", self.base_dir, self.url));
+    }
 
-        if month < 10 {
-            month_str = format!("0{}", month);
+    /// Absolute url of the image shown when sharing this post, the `cover` front matter
+    /// field or the first image in the post, falling back to the site icon.
+    pub fn get_cover_url(&self) -> String {
+        if self.cover.is_empty() {
+            return format!("{}/static/img/icon.png", SITE_URL);
+        } else if self.cover.starts_with("http://") || self.cover.starts_with("https://") {
+            return self.cover.clone();
+        } else if self.cover.starts_with('/') {
+            return format!("{}{}", SITE_URL, self.cover);
         }
 
-        if day < 10 {
-            day_str = format!("0{}", day);
-        }
+        // relative to the post:
+        return format!("{}{}", self.get_absolute_url(), self.cover);
+    }
 
-        return format!(
-            "{}-{}-{}T{}.000Z",
-            date.year(),
-            month_str,
-            day_str,
-            date.time()
+    /// schema.org `BlogPosting` of this post as JSON-LD, safe to embed in a `<script>`
+    /// element as is.
+    pub fn get_json_ld(&self) -> String {
+        let keywords: Vec<String> = self
+            .get_topics()
+            .iter()
+            .map(|topic| get_json_string(topic))
+            .collect();
+
+        let mut json = String::from("{\n");
+        json += "        \"@context\": \"https://schema.org\",\n";
+        json += "        \"@type\": \"BlogPosting\",\n";
+        json += &format!("        \"headline\": {},\n", get_json_string(&self.title));
+        json += &format!(
+            "        \"description\": {},\n",
+            get_json_string(&self.snippet)
+        );
+        json += &format!(
+            "        \"image\": {},\n",
+            get_json_string(&self.get_cover_url())
+        );
+        json += &format!(
+            "        \"datePublished\": {},\n",
+            get_json_string(&Helper::get_date_for_html(&self.published))
+        );
+        json += &format!(
+            "        \"dateModified\": {},\n",
+            get_json_string(&Helper::get_date_for_html(&self.updated))
+        );
+        json += &format!(
+            "        \"author\": {{ \"@type\": \"Person\", \"name\": {} }},\n",
+            get_json_string(&self.author)
+        );
+        json += &format!(
+            "        \"publisher\": {{ \"@type\": \"Organization\", \"name\": \"luflow.net\", \"logo\": {{ \"@type\": \"ImageObject\", \"url\": {} }} }},\n",
+            get_json_string(&format!("{}/static/img/icon.png", SITE_URL))
+        );
+        json += &format!("        \"keywords\": [{}],\n", keywords.join(", "));
+        json += &format!(
+            "        \"mainEntityOfPage\": {},\n",
+            get_json_string(&self.get_absolute_url())
         );
+        json += &format!(
+            "        \"url\": {}\n",
+            get_json_string(&self.get_absolute_url())
+        );
+        json += "    }";
+
+        // a '</script>' in any value would end the script element early:
+        return json.replace("</", "<\\/");
     }
 
-    pub fn create_output_dir(&self) {
-        Helper::create_dir_all(
-            &Helper::get_output_dir()
-                .join(&self.base_dir)
-                .join(&self.url),
-        );
+    pub fn create_output_dir(&self, paths: &SitePaths) -> Result<(), SiteError> {
+        return Helper::create_dir_all(&paths.output_dir.join(&self.base_dir).join(&self.url));
+    }
+
+    pub fn get_output_file(&self, paths: &SitePaths) -> PathBuf {
+        return paths
+            .output_dir
+            .join(&self.base_dir)
+            .join(&self.url)
+            .join("index.html");
+    }
+
+    /// Fields shown where this post is listed (overviews, archive, index), for runtime
+    /// templates.
+    pub fn get_summary_context(&self) -> Value {
+        let topics: Vec<Value> = self
+            .topics
+            .iter()
+            .zip(&self.topics_sanitized)
+            .map(|(topic, topic_sanitized)| {
+                Value::map(vec![
+                    ("name", Value::from(topic)),
+                    (
+                        "url",
+                        Value::from(format!(
+                            "{}/{}/{}",
+                            self.base_dir, self.topic_base_dir, topic_sanitized
+                        )),
+                    ),
+                ])
+            })
+            .collect();
+
+        return Value::map(vec![
+            ("title", Value::from(&self.title)),
+            (
+                "url",
+                Value::from(format!("{}/{}", self.base_dir, self.url)),
+            ),
+            ("author", Value::from(&self.author)),
+            ("snippet", Value::from(&self.snippet)),
+            ("cover", Value::from(&self.cover)),
+            ("topics", Value::from(topics)),
+            (
+                "published",
+                Value::from(Helper::format_date(&self.published, &self.date_format)),
+            ),
+            (
+                "published_html",
+                Value::from(Helper::get_date_for_html(&self.published)),
+            ),
+            (
+                "updated",
+                Value::from(Helper::format_date(&self.updated, &self.date_format)),
+            ),
+            (
+                "updated_html",
+                Value::from(Helper::get_date_for_html(&self.updated)),
+            ),
+            ("is_updated", Value::from(self.is_updated())),
+        ]);
+    }
+
+    pub fn generate(
+        &self,
+        paths: &SitePaths,
+        cache: &BuildCache,
+        theme: &Theme,
+    ) -> Result<(), SiteError> {
+        let output_file = self.get_output_file(paths);
+        let html = theme.render(self, &output_file)?;
+
+        return cache.write_file_sync(&output_file, html.as_bytes());
+    }
+}
+
+impl ThemeTemplate for BlogPost {
+    fn get_template_name(&self) -> &str {
+        return "blog_post.html";
+    }
+
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
     }
 
-    pub fn generate(&self) {
-        Helper::write_file_sync(
-            &Helper::get_output_dir()
-                .join(&self.base_dir)
-                .join(&self.url)
-                .join("index.html"),
-            &self.render().unwrap().as_bytes(),
-        )
-        .unwrap();
+    fn get_context(&self) -> Value {
+        let mut context = self.get_summary_context();
+        context.insert("base_dir", Value::from(&self.base_dir));
+        context.insert("keywords", Value::from(&self.topics_comma_separated));
+        context.insert("absolute_url", Value::from(self.get_absolute_url()));
+        context.insert("cover_url", Value::from(self.get_cover_url()));
+        context.insert("json_ld", Value::from(self.get_json_ld()));
+        context.insert("html", Value::from(&self.html));
+
+        return context;
     }
 }
 
-pub async fn parse_markdown_file(path: PathBuf, base_dir: String) -> BlogPost {
-    let contents = tokio::fs::read_to_string(path).await.unwrap();
+pub async fn parse_markdown_file(
+    path: PathBuf,
+    base_dir: String,
+    taxonomy_names: Vec<String>,
+    timezone: Timezone,
+    date_format: String,
+    menu: Arc<Menu>,
+) -> Result<BlogPost, SiteError> {
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| SiteError::io("read", &path, e))?;
     let mut post_start_found = false;
     let mut markdown = String::new();
     let mut blog_post = BlogPost::new(base_dir.clone());
+    blog_post.source_path = path.clone();
+    blog_post.date_format = date_format;
+    blog_post.menu = menu;
 
-    for line in contents.lines() {
-        if !post_start_found && line.len() > 0 {
+    for (i, line) in contents.lines().enumerate() {
+        let error = |message: &str| SiteError::parse(&path, Some(i + 1), message);
+        if !post_start_found && !line.is_empty() {
             if line == "---" {
                 post_start_found = true;
                 continue;
             }
 
             let v: Vec<&str> = line.splitn(2, ':').collect();
-            assert_eq!(v.len(), 2);
+            if v.len() != 2 {
+                return Err(error("Expected a line like 'key: value'."));
+            }
 
-            if let Some(key) = v.get(0) {
+            if let Some(key) = v.first() {
                 if *key == "author" {
                     if let Some(value) = v.get(1) {
                         blog_post.author = String::from(value.trim());
                     } else {
-                        panic!("Unable to parse field: 'author'.");
+                        return Err(error("Unable to parse field: 'author'."));
                     }
                 } else if *key == "published" {
-                    if let Some(value) = v.get(1) {
-                        blog_post.published =
-                            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").unwrap();
-                        blog_post.published_for_feed =
-                            blog_post.get_date_for_feed(&blog_post.published);
-                    } else {
-                        panic!("Unable to parse field: 'published'.");
+                    match v
+                        .get(1)
+                        .and_then(|value| parse_date(value.trim(), timezone))
+                    {
+                        Some(value) => blog_post.published = value,
+                        _ => return Err(error("Unable to parse field: 'published'.")),
                     }
                 } else if *key == "updated" {
-                    if let Some(value) = v.get(1) {
-                        blog_post.updated =
-                            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").unwrap();
-                        blog_post.updated_for_feed =
-                            blog_post.get_date_for_feed(&blog_post.updated);
-                    } else {
-                        panic!("Unable to parse field: 'updated'.");
+                    match v
+                        .get(1)
+                        .and_then(|value| parse_date(value.trim(), timezone))
+                    {
+                        Some(value) => blog_post.updated = value,
+                        _ => return Err(error("Unable to parse field: 'updated'.")),
                     }
                 } else if *key == "topics" {
                     if let Some(value) = v.get(1) {
                         blog_post.topics_comma_separated = String::from(value.trim());
                         let v = blog_post.topics_comma_separated.split(",");
                         for topic in v {
-                            let topic_trimmed = topic.trim();
-                            blog_post.topics.push(String::from(topic_trimmed));
+                            let topic_normalized = normalize_topic(topic);
+                            if topic_normalized.is_empty() {
+                                continue;
+                            }
                             blog_post
                                 .topics_sanitized
-                                .push(Helper::sanitize_string(topic_trimmed));
+                                .push(Helper::sanitize_path(&topic_normalized));
+                            blog_post.topics.push(topic_normalized);
                         }
                     } else {
-                        panic!("Unable to parse field: 'topics'.");
+                        return Err(error("Unable to parse field: 'topics'."));
                     }
                 } else if *key == "title" {
                     if let Some(value) = v.get(1) {
                         blog_post.title = String::from(value.trim());
                         blog_post.url = Helper::sanitize_string(&blog_post.title);
                     } else {
-                        panic!("Unable to parse field: 'title'.");
+                        return Err(error("Unable to parse field: 'title'."));
                     }
                 } else if *key == "snippet" {
                     if let Some(value) = v.get(1) {
                         blog_post.snippet = String::from(value.trim());
                     } else {
-                        panic!("Unable to parse field: 'snippet'.");
+                        return Err(error("Unable to parse field: 'snippet'."));
+                    }
+                } else if *key == "cover" {
+                    if let Some(value) = v.get(1) {
+                        blog_post.cover = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'cover'."));
+                    }
+                } else if *key == "draft" {
+                    match v.get(1).map(|value| value.trim()) {
+                        Some("true") => blog_post.draft = true,
+                        Some("false") => blog_post.draft = false,
+                        _ => return Err(error("Unable to parse field: 'draft'.")),
+                    }
+                } else if taxonomy_names.iter().any(|name| name == key) {
+                    if let Some(value) = v.get(1) {
+                        let mut terms = Vec::new();
+                        for term in value.split(',') {
+                            let term_normalized = normalize_topic(term);
+                            if !term_normalized.is_empty() {
+                                terms.push(term_normalized);
+                            }
+                        }
+                        blog_post.taxonomies.insert(String::from(*key), terms);
+                    } else {
+                        return Err(error(&format!("Unable to parse field: '{}'.", key)));
                     }
                 }
             }
         } else {
-            if markdown.len() > 0 {
+            if !markdown.is_empty() {
                 markdown += "\n";
             }
             markdown += line;
@@ -188,5 +393,73 @@ pub async fn parse_markdown_file(path: PathBuf, base_dir: String) -> BlogPost {
     // convert markdown to html:
     blog_post.html = markdown::to_html(&markdown);
 
-    return blog_post;
+    // use first image in post as cover if none is given:
+    if blog_post.cover.is_empty() {
+        let re_image = Regex::new(r#"<img src="([^"]+)""#).unwrap();
+        if let Some(captures) = re_image.captures(&blog_post.html) {
+            blog_post.cover = String::from(&captures[1]);
+        }
+    }
+
+    return Ok(blog_post);
+}
+
+/// Creates a new draft blog post in 'blog-posts' of the site at `paths` titled `title`,
+/// published now and by the author of the latest post. Returns the path of the created
+/// file.
+pub fn create_blog_post_file(paths: &SitePaths, title: &str) -> Result<PathBuf, SiteError> {
+    let now = Local::now();
+    let file_name = format!(
+        "{}-{}.md",
+        now.format("%Y-%m-%d-%H:%M"),
+        Helper::sanitize_string(title.trim())
+    );
+    let path = paths.get_blog_posts_dir().join(file_name);
+    if path.exists() {
+        return Err(SiteError::content(&format!(
+            "Blog post '{}' already exists.",
+            path.display()
+        )));
+    }
+
+    // file names start with the publish date, so the last one is the latest post:
+    let mut author = String::new();
+    if let Some(latest) = Helper::get_files_recursive(&paths.get_blog_posts_dir())?
+        .iter()
+        .max()
+        && let Ok(contents) = std::fs::read_to_string(latest)
+        && let Some(value) = contents
+            .lines()
+            .find_map(|line| line.strip_prefix("author:"))
+    {
+        author = String::from(value.trim());
+    }
+
+    let date = now.format("%Y-%m-%d %H:%M:00");
+    let contents = format!(
+        "author: {}\npublished: {}\nupdated: {}\ntopics: \ntitle: {}\nsnippet: \ndraft: true\n\n---\n\n",
+        author,
+        date,
+        date,
+        title.trim()
+    );
+    std::fs::write(&path, contents).map_err(|e| SiteError::io("write", &path, e))?;
+
+    return Ok(path);
+}
+
+/// Parses a front matter date, either with an UTC offset ('2022-01-13 16:24:00 +01:00'
+/// or '2022-01-13T16:24:00+01:00') or without one ('2022-01-13 16:24:00'), in which
+/// case the date is local time in `timezone` (with daylight saving time, if any).
+fn parse_date(value: &str, timezone: Timezone) -> Option<DateTime<FixedOffset>> {
+    if let Ok(date) = DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S %z") {
+        return Some(date);
+    }
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date);
+    }
+
+    return NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .and_then(|date| timezone.resolve(&date));
 }
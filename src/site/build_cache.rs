@@ -0,0 +1,339 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::site::error::SiteError;
+use crate::site::helper::{Helper, Verbosity};
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
+use crate::site::theme::Theme;
+
+/// Remembers the hashes of the sources and outputs of the last build in the cache file
+/// ('.flow-web-cache' by default), so that the next build only renders pages whose inputs changed,
+/// only writes files whose content changed and removes outputs that are no longer
+/// generated.
+///
+/// All hashes are only trusted when the build hash (flow-web binary, which contains the
+/// compiled templates, the site config, the runtime templates of the theme and the menu
+/// shown on every page) is the same as last time. The outputs are only trusted for the
+/// output dir they were published to, another `--output` starts with an empty cache.
+pub struct BuildCache {
+    source_dir: PathBuf,
+    output_dir: PathBuf,
+    published_dir: String,
+    cache_file: Option<PathBuf>,
+    build_hash: String,
+    is_build_changed: bool,
+    previous_sources: HashMap<String, String>,
+    previous_outputs: HashMap<String, String>,
+    sources: Mutex<HashMap<String, String>>,
+    outputs: Mutex<HashMap<String, String>>,
+}
+
+impl BuildCache {
+    /// Reads the cache file of the site at `paths` (if any), `use_cache` false starts an
+    /// empty cache that is never saved. `published_dir` is the output dir the staging dir
+    /// at `paths.output_dir` replaces.
+    pub async fn load(
+        paths: &SitePaths,
+        published_dir: &Path,
+        theme: &Theme,
+        menu: &Menu,
+        use_cache: bool,
+    ) -> Result<Self, SiteError> {
+        let mut build_data = get_binary_data().into_bytes();
+        build_data.append(&mut std::fs::read(&paths.config_file).unwrap_or_default());
+        build_data.extend_from_slice(theme.get_build_data());
+        build_data.append(&mut menu.get_build_data());
+        let cache_file = if use_cache {
+            Some(paths.cache_file.clone())
+        } else {
+            None
+        };
+        let build_hash = get_hash(&build_data);
+        let published_dir = get_dir_key(published_dir);
+
+        let mut previous_build_hash = String::new();
+        let mut previous_published_dir = String::new();
+        let mut previous_sources = HashMap::new();
+        let mut previous_outputs = HashMap::new();
+
+        // cache file is optional, everything is (re)built without it:
+        if let Some(path) = &cache_file
+            && Helper::exists_dir(path)
+        {
+            let contents = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| SiteError::io("read", path, e))?;
+            for (i, line) in contents.lines().enumerate() {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                // 'published <dir>', the dir may contain spaces:
+                if let Some(dir) = line.strip_prefix("published ") {
+                    previous_published_dir = String::from(dir);
+                    continue;
+                }
+
+                // '<kind> <hash> <path>', path last as it may contain spaces:
+                let v: Vec<&str> = line.splitn(3, ' ').collect();
+                match (v.first(), v.get(1), v.get(2)) {
+                    (Some(&"build"), Some(hash), None) => previous_build_hash = String::from(*hash),
+                    (Some(&"source"), Some(hash), Some(key)) => {
+                        previous_sources.insert(String::from(*key), String::from(*hash));
+                    }
+                    (Some(&"output"), Some(hash), Some(key)) => {
+                        previous_outputs.insert(String::from(*key), String::from(*hash));
+                    }
+                    _ => {
+                        return Err(SiteError::parse(
+                            path,
+                            Some(i + 1),
+                            "Unable to parse build cache line, delete the file for a clean build.",
+                        ));
+                    }
+                }
+            }
+        }
+
+        // the hashes of another output dir tell nothing about the files in this one:
+        if previous_published_dir != published_dir {
+            previous_sources.clear();
+            previous_outputs.clear();
+        }
+
+        return Ok(Self {
+            source_dir: paths.source_dir.clone(),
+            output_dir: paths.output_dir.clone(),
+            published_dir,
+            cache_file,
+            is_build_changed: build_hash != previous_build_hash,
+            build_hash,
+            previous_sources,
+            previous_outputs,
+            sources: Mutex::new(HashMap::new()),
+            outputs: Mutex::new(HashMap::new()),
+        });
+    }
+
+    /// Returns true if nothing was cached yet, so the output dir can't be trusted.
+    pub fn is_empty(&self) -> bool {
+        return self.previous_outputs.is_empty();
+    }
+
+    /// Records the source at `source_path` (e.g. a blog post) with `data` as content and
+    /// returns true if it changed since the last build, i.e. pages made from it must be
+    /// rendered again.
+    pub fn is_source_changed(&self, source_path: &Path, data: &[u8]) -> bool {
+        let key = get_relative_key(source_path, &self.source_dir);
+        let hash = get_hash(data);
+        let is_changed = self.is_build_changed || self.previous_sources.get(&key) != Some(&hash);
+        self.sources.lock().unwrap().insert(key, hash);
+
+        return is_changed;
+    }
+
+    /// Keeps the output at `file_path` from the last build without rendering it again,
+    /// returns false if it is missing and must be generated after all.
+    pub fn keep_file(&self, file_path: &Path) -> bool {
+        let key = get_relative_key(file_path, &self.output_dir);
+        if let Some(hash) = self.previous_outputs.get(&key)
+            && file_path.exists()
+        {
+            Helper::print(
+                Verbosity::Verbose,
+                &format!("Kept unchanged '{}'", file_path.display()),
+            );
+            self.outputs.lock().unwrap().insert(key, hash.clone());
+            return true;
+        }
+
+        return false;
+    }
+
+    /// Writes `data` to `file_path` unless the file already has that content.
+    pub fn write_file_sync(&self, file_path: &PathBuf, data: &[u8]) -> Result<(), SiteError> {
+        if self.record_output(file_path, get_hash(data)) {
+            Helper::write_file_sync(file_path, data)
+                .map_err(|e| SiteError::io("write", file_path, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Async version of `write_file_sync`.
+    pub async fn write_file(&self, file_path: &PathBuf, data: &[u8]) -> Result<(), SiteError> {
+        if self.record_output(file_path, get_hash(data)) {
+            Helper::write_file(file_path, data)
+                .await
+                .map_err(|e| SiteError::io("write", file_path, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies all files in the `(src, dst)` dirs of `dirs` recursively, skipping files that
+    /// are unchanged since the last build. A file of a later dir replaces the file of an
+    /// earlier dir with the same destination, each destination is only written once.
+    pub async fn copy_dirs_all(&self, dirs: &[(PathBuf, PathBuf)]) -> Result<(), SiteError> {
+        // destination file -> source file, merged before anything is written:
+        let mut files = BTreeMap::new();
+        for (src, dst) in dirs {
+            for src_file in Helper::get_files_recursive(src)? {
+                let dst_file = dst.join(Helper::get_relative_path(&src_file, src)?);
+                files.insert(dst_file, src_file);
+            }
+        }
+
+        for (dst_file, src_file) in files {
+            let data = tokio::fs::read(&src_file)
+                .await
+                .map_err(|e| SiteError::io("read", &src_file, e))?;
+
+            if self.record_output(&dst_file, get_hash(&data)) {
+                if let Some(parent) = dst_file.parent() {
+                    tokio::fs::create_dir_all(parent)
+                        .await
+                        .map_err(|e| SiteError::io("create dir", parent, e))?;
+                }
+                // may be hard linked to the published site, replace instead of writing
+                // through the link:
+                let _ = tokio::fs::remove_file(&dst_file).await;
+                tokio::fs::write(&dst_file, &data)
+                    .await
+                    .map_err(|e| SiteError::io("write", &dst_file, e))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes all outputs of the last build that were neither written nor kept by this
+    /// build, together with directories left empty.
+    pub fn remove_orphans(&self) {
+        let outputs = self.outputs.lock().unwrap();
+        let output_dir = &self.output_dir;
+
+        for key in self.previous_outputs.keys() {
+            if outputs.contains_key(key) {
+                continue;
+            }
+
+            let file_path = output_dir.join(key);
+            if std::fs::remove_file(&file_path).is_ok() {
+                Helper::print(
+                    Verbosity::Normal,
+                    &format!("Removed orphaned '{}'", file_path.display()),
+                );
+            }
+
+            // remove_dir only succeeds for empty dirs:
+            let mut dir = file_path.parent();
+            while let Some(value) = dir
+                && value != output_dir.as_path()
+                && std::fs::remove_dir(value).is_ok()
+            {
+                dir = value.parent();
+            }
+        }
+    }
+
+    /// Writes the hashes of this build to the cache file, unless caching is disabled.
+    pub fn save(&self) -> Result<(), SiteError> {
+        let path = match &self.cache_file {
+            Some(value) => value,
+            _ => return Ok(()),
+        };
+
+        let mut contents = String::from("# flow-web build cache, safe to delete\n");
+        contents += &format!("build {}\n", self.build_hash);
+        contents += &format!("published {}\n", self.published_dir);
+
+        // sorted so that an unchanged build results in an identical cache file:
+        let mut sources: Vec<(String, String)> =
+            self.sources.lock().unwrap().clone().into_iter().collect();
+        sources.sort();
+        for (key, hash) in sources {
+            contents += &format!("source {} {}\n", hash, key);
+        }
+
+        let mut outputs: Vec<(String, String)> =
+            self.outputs.lock().unwrap().clone().into_iter().collect();
+        outputs.sort();
+        for (key, hash) in outputs {
+            contents += &format!("output {} {}\n", hash, key);
+        }
+
+        std::fs::write(path, contents).map_err(|e| SiteError::io("write", path, e))?;
+
+        Ok(())
+    }
+
+    /// Records output `file_path` with content hash `hash`, returns true if it must be
+    /// written.
+    fn record_output(&self, file_path: &Path, hash: String) -> bool {
+        let key = get_relative_key(file_path, &self.output_dir);
+        let is_changed = self.previous_outputs.get(&key) != Some(&hash) || !file_path.exists();
+        if !is_changed {
+            Helper::print(
+                Verbosity::Verbose,
+                &format!("Skipped unchanged '{}'", file_path.display()),
+            );
+        }
+        self.outputs.lock().unwrap().insert(key, hash);
+
+        return is_changed;
+    }
+}
+
+/// Sources and outputs are cached relative to the source and output dir, so the cache
+/// stays valid when the site is moved.
+fn get_relative_key(path: &Path, dir: &Path) -> String {
+    let relative_path = path.strip_prefix(dir).unwrap_or(path);
+
+    return relative_path.to_string_lossy().replace('\\', "/");
+}
+
+/// Identifies the flow-web binary (and so the compiled templates) by version, size and
+/// modification time, much cheaper than hashing all of it.
+fn get_binary_data() -> String {
+    let metadata = std::env::current_exe().and_then(std::fs::metadata);
+    let (size, modified) = match &metadata {
+        Ok(value) => (
+            value.len(),
+            value
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|duration| duration.as_nanos())
+                .unwrap_or_default(),
+        ),
+        _ => (0, 0),
+    };
+
+    return format!("{} {} {}\n", env!("CARGO_PKG_VERSION"), size, modified);
+}
+
+/// The output dir as stored in the cache file, canonicalized so that e.g. 'output' and
+/// './output' are the same dir. Absolute only if it doesn't exist yet.
+fn get_dir_key(dir: &Path) -> String {
+    let dir = std::fs::canonicalize(dir)
+        .or_else(|_| std::path::absolute(dir))
+        .unwrap_or_else(|_| dir.to_path_buf());
+
+    return dir.to_string_lossy().into_owned();
+}
+
+/// 64 bit FNV-1a hash of `data` as hex string, stable across builds and platforms.
+fn get_hash(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    return format!("{:016x}", hash);
+}
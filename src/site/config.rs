@@ -0,0 +1,241 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use chrono_tz::Tz;
+use std::path::PathBuf;
+
+use crate::site::error::SiteError;
+use crate::site::feed::FeedFormat;
+use crate::site::helper::Helper;
+
+/// Public url of the site, used for absolute links in feeds and sitemaps.
+pub const SITE_URL: &str = "https://www.luflow.net";
+
+/// Front matter keys already in use by blog posts and dirs generated next to taxonomy
+/// pages ('blog/<name>/') and feeds ('feeds/<name>/'), these can't be used as taxonomy
+/// names.
+const RESERVED_TAXONOMY_NAMES: [&str; 18] = [
+    // front matter keys:
+    "author",
+    "published",
+    "updated",
+    "topic",
+    "topics",
+    "year",
+    "title",
+    "snippet",
+    "cover",
+    "draft",
+    // dirs of the blog and the site:
+    "page",
+    "archive",
+    "blog",
+    "feeds",
+    "search",
+    "projects",
+    "screenshots",
+    "static",
+];
+
+/// Time zone of blog post dates that don't give an UTC offset, 'timezone' in 'site.conf'.
+#[derive(Clone, Copy, Debug)]
+pub enum Timezone {
+    /// Fixed UTC offset, e.g. '+01:00'.
+    Offset(FixedOffset),
+    /// IANA time zone with its daylight saving time, e.g. 'Europe/Stockholm'.
+    Zone(Tz),
+}
+
+impl Timezone {
+    /// Resolves local time `date` in this time zone with the offset in effect at that
+    /// time. An ambiguous time (when clocks are set back) is the earlier one, a time that
+    /// doesn't exist (when clocks are set forward) is None.
+    pub fn resolve(&self, date: &NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        return match self {
+            Timezone::Offset(offset) => date.and_local_timezone(*offset).single(),
+            Timezone::Zone(zone) => date
+                .and_local_timezone(*zone)
+                .earliest()
+                .map(|value| value.fixed_offset()),
+        };
+    }
+}
+
+/// Site wide settings read from 'site.conf' in the root folder.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub taxonomies: Vec<String>,
+    pub taxonomy_feeds: Vec<String>,
+    pub feed_formats: Vec<FeedFormat>,
+    pub feed_max_entries: usize,
+    pub timezone: Timezone,
+    pub date_format: String,
+    pub theme: Option<String>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self {
+            taxonomies: Vec::new(),
+            taxonomy_feeds: Vec::new(),
+            feed_formats: vec![FeedFormat::Atom],
+            feed_max_entries: 20,
+            timezone: Timezone::Offset(FixedOffset::east_opt(0).unwrap()),
+            date_format: String::from("%-d %B %Y"),
+            theme: None,
+        }
+    }
+}
+
+pub async fn parse_config_file(path: PathBuf) -> Result<Config, SiteError> {
+    let mut config = Config::new();
+
+    // config file is optional, use defaults if not available:
+    if !Helper::exists_dir(&path) {
+        return Ok(config);
+    }
+
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| SiteError::io("read", &path, e))?;
+
+    for (i, line) in contents.lines().enumerate() {
+        let error = |message: &str| SiteError::parse(&path, Some(i + 1), message);
+        let line_trimmed = line.trim();
+        if line_trimmed.is_empty() || line_trimmed.starts_with('#') {
+            continue;
+        }
+
+        let v: Vec<&str> = line_trimmed.splitn(2, ':').collect();
+        if v.len() != 2 {
+            return Err(error("Expected a line like 'key: value'."));
+        }
+
+        if let Some(key) = v.first() {
+            if *key == "taxonomies" {
+                if let Some(value) = v.get(1) {
+                    for taxonomy in value.split(',') {
+                        let taxonomy_sanitized = Helper::sanitize_string(taxonomy.trim());
+                        if taxonomy_sanitized.is_empty() {
+                            continue;
+                        }
+                        if RESERVED_TAXONOMY_NAMES.contains(&taxonomy_sanitized.as_str()) {
+                            return Err(error(&format!(
+                                "Taxonomy name: '{}' is reserved.",
+                                taxonomy_sanitized
+                            )));
+                        }
+                        config.taxonomies.push(taxonomy_sanitized);
+                    }
+                } else {
+                    return Err(error("Unable to parse field: 'taxonomies'."));
+                }
+            } else if *key == "taxonomy_feeds" {
+                if let Some(value) = v.get(1) {
+                    for taxonomy in value.split(',') {
+                        let taxonomy_sanitized = Helper::sanitize_string(taxonomy.trim());
+                        if !taxonomy_sanitized.is_empty() {
+                            config.taxonomy_feeds.push(taxonomy_sanitized);
+                        }
+                    }
+                } else {
+                    return Err(error("Unable to parse field: 'taxonomy_feeds'."));
+                }
+            } else if *key == "feed_formats" {
+                if let Some(value) = v.get(1) {
+                    config.feed_formats.clear();
+                    for format in value.split(',') {
+                        let format_trimmed = format.trim();
+                        if format_trimmed.is_empty() {
+                            continue;
+                        }
+                        match FeedFormat::from_name(format_trimmed) {
+                            Some(feed_format) => config.feed_formats.push(feed_format),
+                            _ => {
+                                return Err(error(&format!(
+                                    "Unknown feed format: '{}'.",
+                                    format_trimmed
+                                )));
+                            }
+                        }
+                    }
+                } else {
+                    return Err(error("Unable to parse field: 'feed_formats'."));
+                }
+            } else if *key == "feed_max_entries" {
+                match v.get(1).map(|value| value.trim().parse::<usize>()) {
+                    Some(Ok(value)) => config.feed_max_entries = value,
+                    _ => return Err(error("Unable to parse field: 'feed_max_entries'.")),
+                }
+            } else if *key == "timezone" {
+                // an UTC offset or the name of an IANA time zone:
+                let value = v.get(1).map(|value| value.trim()).unwrap_or_default();
+                if let Some(offset) = parse_utc_offset(value) {
+                    config.timezone = Timezone::Offset(offset);
+                } else if let Ok(zone) = value.parse::<Tz>() {
+                    config.timezone = Timezone::Zone(zone);
+                } else {
+                    return Err(error("Unable to parse field: 'timezone'."));
+                }
+            } else if *key == "date_format" {
+                match v.get(1).map(|value| value.trim()) {
+                    Some(value)
+                        if !value.is_empty()
+                            && !StrftimeItems::new(value).any(|item| item == Item::Error) =>
+                    {
+                        config.date_format = String::from(value)
+                    }
+                    _ => return Err(error("Unable to parse field: 'date_format'.")),
+                }
+            } else if *key == "theme" {
+                // theme name is a dir in 'themes', nothing else:
+                match v.get(1).map(|value| value.trim()) {
+                    Some("") => config.theme = None,
+                    Some(value) if Helper::sanitize_string(value) == value => {
+                        config.theme = Some(String::from(value))
+                    }
+                    _ => return Err(error("Unable to parse field: 'theme'.")),
+                }
+            } else {
+                return Err(error(&format!("Unknown config field: '{}'.", key)));
+            }
+        }
+    }
+
+    return Ok(config);
+}
+
+/// Parses an UTC offset like '+01:00', '-0530' or 'UTC'.
+///
+/// # Examples
+///
+/// ```
+/// "+01:00" -> Some(UTC+1)
+/// "-0530"  -> Some(UTC-5:30)
+/// "Z"      -> Some(UTC)
+/// "CET"    -> None
+/// ```
+pub fn parse_utc_offset(str: &str) -> Option<FixedOffset> {
+    if str == "Z" || str.eq_ignore_ascii_case("UTC") {
+        return FixedOffset::east_opt(0);
+    }
+
+    let sign = match str.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return None,
+    };
+    let digits = str[1..].replace(':', "");
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+
+    return FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60));
+}
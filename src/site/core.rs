@@ -2,22 +2,29 @@
 // AGPL-3.0 License (see LICENSE)
 
 use sailfish::Template;
-use sailfish::TemplateSimple;
-use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use crate::site::blog_post::BlogPost;
-use crate::site::helper::Helper;
+use crate::site::build_cache::BuildCache;
+use crate::site::error::SiteError;
+use crate::site::helper::{Helper, Verbosity};
+use crate::site::menu::Menu;
+use crate::site::page::Page;
+use crate::site::paths::SitePaths;
+use crate::site::project::Project;
 use crate::site::screenshot::Screenshot;
+use crate::site::sitemap::SitemapShared;
+use crate::site::template::Value;
+use crate::site::theme::{Theme, ThemeTemplate};
 
 pub struct CoreShared {
     state: Mutex<CoreState>,
 }
 
 impl CoreShared {
-    pub fn new() -> Self {
+    pub fn new(menu: Arc<Menu>) -> Self {
         Self {
-            state: Mutex::new(CoreState::new()),
+            state: Mutex::new(CoreState::new(menu)),
         }
     }
 
@@ -25,11 +32,13 @@ impl CoreShared {
         &self,
         blog_base_dir: String,
         blog_posts: Vec<BlogPost>,
+        featured_project: Option<Project>,
         screenshots: Vec<Screenshot>,
     ) {
         let mut lock = self.state.lock().unwrap();
         lock.blog_base_dir = blog_base_dir;
         lock.blog_posts = blog_posts;
+        lock.featured_project = featured_project;
         lock.screenshots = screenshots;
     }
 }
@@ -38,132 +47,168 @@ impl CoreShared {
 #[template(path = "index.stpl")]
 struct CoreState {
     pub blog_posts: Vec<BlogPost>,
+    pub featured_project: Option<Project>,
     pub screenshots: Vec<Screenshot>,
-    pub hfge_url: String,
     pub blog_base_dir: String,
+    pub menu: Arc<Menu>,
 }
 
 impl CoreState {
-    pub fn new() -> Self {
+    pub fn new(menu: Arc<Menu>) -> Self {
         Self {
             blog_posts: Vec::new(),
+            featured_project: None,
             screenshots: Vec::new(),
-            hfge_url: String::from("projects/hfge"),
             blog_base_dir: String::new(),
+            menu,
         }
     }
 }
 
-pub async fn generate_core() {
-    // create output dirs needed:
-    create_output_dirs();
-
-    let mut tasks = Vec::with_capacity(4);
-
-    // copy all static related files:
-    tasks.push(tokio::spawn(copy_static_dirs()));
+impl ThemeTemplate for CoreState {
+    fn get_template_name(&self) -> &str {
+        return "index.html";
+    }
 
-    // generate all core pages (core index will be done as the very
-    // last thing as screenshot and blog generation must be done first):
-    tasks.push(tokio::spawn(generate_error_pages()));
-    tasks.push(tokio::spawn(generate_project_pages()));
-    tasks.push(tokio::spawn(generate_contact_page()));
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
 
-    // wait until all taks are done:
-    for task in tasks {
-        task.await.unwrap();
+    fn get_context(&self) -> Value {
+        return Value::map(vec![
+            (
+                "blog_posts",
+                Value::from(
+                    self.blog_posts
+                        .iter()
+                        .map(|post| post.get_summary_context())
+                        .collect::<Vec<Value>>(),
+                ),
+            ),
+            (
+                "screenshots",
+                Value::from(
+                    self.screenshots
+                        .iter()
+                        .map(|screenshot| screenshot.get_summary_context())
+                        .collect::<Vec<Value>>(),
+                ),
+            ),
+            (
+                "featured_project",
+                match &self.featured_project {
+                    Some(project) => project.get_summary_context(),
+                    _ => Value::Null,
+                },
+            ),
+            ("blog_base_dir", Value::from(&self.blog_base_dir)),
+        ]);
     }
 }
 
-fn create_output_dirs() {
-    Helper::create_dir_all(&Helper::get_output_dir().join("contact"));
-    Helper::create_dir_all(&Helper::get_output_dir().join("projects/hfge"));
+pub async fn generate_core(
+    paths: Arc<SitePaths>,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: Arc<Theme>,
+    pages: Vec<Page>,
+) -> Result<(), SiteError> {
+    let tasks = vec![
+        // copy all static related files:
+        tokio::spawn(copy_static_dirs(
+            paths.clone(),
+            cache.clone(),
+            theme.clone(),
+        )),
+        // generate all pages in 'pages' (core index will be done as the very
+        // last thing as screenshot and blog generation must be done first):
+        tokio::spawn(generate_pages(
+            paths.clone(),
+            sitemap.clone(),
+            cache.clone(),
+            theme.clone(),
+            pages,
+        )),
+    ];
+
+    // wait until all tasks are done, also if one of them failed:
+    Helper::join_all(tasks).await?;
+
+    Ok(())
 }
 
-async fn copy_static_dirs() {
-    // copy static and static_root to output folder:
-    Helper::copy_dir_all(Path::new("static"), Helper::get_output_dir().join("static"))
-        .await
-        .unwrap();
-    println!(
-        "Copied dir 'static' recursively to '{}'",
-        Helper::get_output_dir().join("static").display()
-    );
-
-    Helper::copy_dir_all(Path::new("static_root"), Helper::get_output_dir())
-        .await
-        .unwrap();
-    println!(
-        "Copied dir 'static_root' recursively to '{}'",
-        Helper::get_output_dir().display()
-    );
-}
+async fn copy_static_dirs(
+    paths: Arc<SitePaths>,
+    cache: Arc<BuildCache>,
+    theme: Arc<Theme>,
+) -> Result<(), SiteError> {
+    // static and static_root (both optional) of the site, static files of the theme (if
+    // any) replace the ones of the site:
+    let mut dirs = Vec::new();
+    if Helper::exists_dir(&paths.get_static_dir()) {
+        dirs.push((paths.get_static_dir(), paths.output_dir.join("static")));
+    }
+    if let Some(theme_static_dir) = theme.get_static_dir(&paths) {
+        dirs.push((theme_static_dir, paths.output_dir.join("static")));
+    }
+    if Helper::exists_dir(&paths.get_static_root_dir()) {
+        dirs.push((paths.get_static_root_dir(), paths.output_dir.clone()));
+    }
 
-async fn generate_error_pages() {
-    // 404:
-    #[derive(TemplateSimple)]
-    #[template(path = "404.stpl")]
-    struct Err404Template {}
-
-    let ctx = Err404Template {};
-    Helper::write_file(
-        &Helper::get_output_dir().join("404.html"),
-        &ctx.render_once().unwrap().as_bytes(),
-    )
-    .await
-    .unwrap();
-
-    // 500:
-    #[derive(TemplateSimple)]
-    #[template(path = "500.stpl")]
-    struct Err500Template {}
-
-    let ctx = Err500Template {};
-    Helper::write_file(
-        &Helper::get_output_dir().join("500.html"),
-        &ctx.render_once().unwrap().as_bytes(),
-    )
-    .await
-    .unwrap();
-}
+    // copy them to output folder (only files changed since last build):
+    cache.copy_dirs_all(&dirs).await?;
+    for (src, dst) in &dirs {
+        Helper::print(
+            Verbosity::Normal,
+            &format!(
+                "Copied dir '{}' recursively to '{}'",
+                src.display(),
+                dst.display()
+            ),
+        );
+    }
 
-async fn generate_project_pages() {
-    // projects/hfge:
-    #[derive(TemplateSimple)]
-    #[template(path = "hfge.stpl")]
-    struct HFGETemplate {}
-
-    let ctx = HFGETemplate {};
-    Helper::write_file(
-        &Helper::get_output_dir().join("projects/hfge/index.html"),
-        &ctx.render_once().unwrap().as_bytes(),
-    )
-    .await
-    .unwrap();
+    Ok(())
 }
 
-async fn generate_contact_page() {
-    // contact:
-    #[derive(TemplateSimple)]
-    #[template(path = "contact.stpl")]
-    struct ContactTemplate {}
-
-    let ctx = ContactTemplate {};
-    Helper::write_file(
-        &Helper::get_output_dir().join("contact/index.html"),
-        &ctx.render_once().unwrap().as_bytes(),
-    )
-    .await
-    .unwrap();
+async fn generate_pages(
+    paths: Arc<SitePaths>,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: Arc<Theme>,
+    pages: Vec<Page>,
+) -> Result<(), SiteError> {
+    for page in &pages {
+        // only render pages whose markdown file changed since the last build:
+        let source = std::fs::read(&page.source_path)
+            .map_err(|e| SiteError::io("read", &page.source_path, e))?;
+        if cache.is_source_changed(&page.source_path, &source)
+            || !cache.keep_file(&page.get_output_file(&paths))
+        {
+            page.generate(&paths, &cache, &theme).await?;
+        }
+        if page.is_listed() {
+            sitemap.add_page(&page.url, None);
+        }
+    }
+
+    Ok(())
 }
 
-pub async fn generate_root_index(shared: Arc<CoreShared>) {
+pub async fn generate_root_index(
+    shared: Arc<CoreShared>,
+    paths: Arc<SitePaths>,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: Arc<Theme>,
+) -> Result<(), SiteError> {
     let lock = shared.state.lock().unwrap();
 
     // write page to disk:
-    Helper::write_file_sync(
-        &Helper::get_output_dir().join("index.html"),
-        &lock.render().unwrap().as_bytes(),
-    )
-    .unwrap();
+    let output_file = paths.output_dir.join("index.html");
+    let html = theme.render(&*lock, &output_file)?;
+    cache.write_file_sync(&output_file, html.as_bytes())?;
+    sitemap.add_page("", lock.blog_posts.iter().map(|post| post.updated).max());
+
+    Ok(())
 }
@@ -0,0 +1,122 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Everything that makes a build fail, with the file it happened in so it can be fixed.
+#[derive(Debug)]
+pub enum SiteError {
+    /// Reading, writing, creating or removing `path` failed.
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// Source file `path` (blog post, topic, screenshots, site config, ...) is invalid,
+    /// `line` is 1-based if known.
+    Parse {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    /// Rendering the page written to `path` failed.
+    Render {
+        path: PathBuf,
+        source: sailfish::RenderError,
+    },
+    /// Sources are valid on their own but don't fit together, e.g. a pinned blog post
+    /// that does not exist.
+    Content { message: String },
+    /// A generator task panicked or was cancelled.
+    Task { source: tokio::task::JoinError },
+    /// The preview server can't listen on `address`.
+    Listen { address: String, source: io::Error },
+}
+
+impl SiteError {
+    pub fn io(action: &'static str, path: &Path, source: io::Error) -> Self {
+        return SiteError::Io {
+            action,
+            path: path.to_path_buf(),
+            source,
+        };
+    }
+
+    pub fn parse(path: &Path, line: Option<usize>, message: &str) -> Self {
+        return SiteError::Parse {
+            path: path.to_path_buf(),
+            line,
+            message: String::from(message),
+        };
+    }
+
+    pub fn render(path: &Path, source: sailfish::RenderError) -> Self {
+        return SiteError::Render {
+            path: path.to_path_buf(),
+            source,
+        };
+    }
+
+    pub fn listen(address: &str, source: io::Error) -> Self {
+        return SiteError::Listen {
+            address: String::from(address),
+            source,
+        };
+    }
+
+    pub fn content(message: &str) -> Self {
+        return SiteError::Content {
+            message: String::from(message),
+        };
+    }
+}
+
+impl fmt::Display for SiteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SiteError::Io {
+                action,
+                path,
+                source,
+            } => write!(f, "Unable to {} '{}': {}", action, path.display(), source),
+            SiteError::Parse {
+                path,
+                line: Some(line),
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            SiteError::Parse {
+                path,
+                line: None,
+                message,
+            } => write!(f, "{}: {}", path.display(), message),
+            SiteError::Render { path, source } => {
+                write!(f, "Unable to render '{}': {}", path.display(), source)
+            }
+            SiteError::Content { message } => write!(f, "{}", message),
+            SiteError::Task { source } => write!(f, "Generator task failed: {}", source),
+            SiteError::Listen { address, source } => {
+                write!(f, "Unable to listen on '{}': {}", address, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SiteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return match self {
+            SiteError::Io { source, .. } => Some(source),
+            SiteError::Render { source, .. } => Some(source),
+            SiteError::Task { source } => Some(source),
+            SiteError::Listen { source, .. } => Some(source),
+            _ => None,
+        };
+    }
+}
+
+impl From<tokio::task::JoinError> for SiteError {
+    fn from(source: tokio::task::JoinError) -> Self {
+        return SiteError::Task { source };
+    }
+}
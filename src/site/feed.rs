@@ -0,0 +1,438 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+
+use crate::site::error::SiteError;
+use crate::site::template::Value;
+use crate::site::xml_writer::XmlWriter;
+
+/// XML namespace of RFC 5005 feed paging and archiving.
+const HISTORY_NAMESPACE: &str = "http://purl.org/syndication/history/1.0";
+
+/// Output formats a feed can be written in, selected with 'feed_formats' in 'site.conf'.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+    Json,
+}
+
+impl FeedFormat {
+    pub fn from_name(name: &str) -> Option<FeedFormat> {
+        return match name {
+            "atom" => Some(FeedFormat::Atom),
+            "rss" => Some(FeedFormat::Rss),
+            "json" => Some(FeedFormat::Json),
+            _ => None,
+        };
+    }
+
+    pub fn get_extension(&self) -> &'static str {
+        return match self {
+            FeedFormat::Atom => "atom",
+            FeedFormat::Rss => "rss",
+            FeedFormat::Json => "json",
+        };
+    }
+
+    pub fn get_mime_type(&self) -> &'static str {
+        return match self {
+            FeedFormat::Atom => "application/atom+xml",
+            FeedFormat::Rss => "application/rss+xml",
+            FeedFormat::Json => "application/feed+json",
+        };
+    }
+
+    pub fn get_title(&self) -> &'static str {
+        return match self {
+            FeedFormat::Atom => "Atom feed",
+            FeedFormat::Rss => "RSS feed",
+            FeedFormat::Json => "JSON feed",
+        };
+    }
+
+    pub fn get_context(&self) -> Value {
+        return Value::map(vec![
+            ("mime_type", Value::from(self.get_mime_type())),
+            ("extension", Value::from(self.get_extension())),
+            ("title", Value::from(self.get_title())),
+        ]);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FeedEntry {
+    pub url: String,
+    pub title: String,
+    pub author: String,
+    pub published: DateTime<FixedOffset>,
+    pub updated: DateTime<FixedOffset>,
+    pub categories: Vec<String>,
+    pub summary: String,
+    pub content: String,
+}
+
+/// Format independent feed, serialized with `to_atom`, `to_rss` or `to_json`.
+#[derive(Clone, Debug)]
+pub struct Feed {
+    pub title: String,
+    pub subtitle: String,
+    pub author: String,
+    pub site_url: String,
+    pub alternate_url: String,
+    pub feed_url: String,
+    pub logo_url: String,
+    pub icon_url: String,
+    pub generator: String,
+    pub entries: Vec<FeedEntry>,
+    pub is_archive: bool,
+    pub current_url: String,
+    pub prev_archive_url: String,
+    pub next_archive_url: String,
+}
+
+impl Feed {
+    pub fn new() -> Self {
+        Self {
+            title: String::new(),
+            subtitle: String::new(),
+            author: String::new(),
+            site_url: String::new(),
+            alternate_url: String::new(),
+            feed_url: String::new(),
+            logo_url: String::new(),
+            icon_url: String::new(),
+            generator: String::new(),
+            entries: Vec::new(),
+            is_archive: false,
+            current_url: String::new(),
+            prev_archive_url: String::new(),
+            next_archive_url: String::new(),
+        }
+    }
+
+    /// Splits the feed into the subscription document holding the newest `max_entries`
+    /// entries and archive documents holding the older ones (RFC 5005 paged archives).
+    /// Archives are numbered from the oldest entries and all but the newest one hold exactly
+    /// `max_entries` entries. New entries only change the newest archive (which fills up
+    /// until another one is started) and the next link of the archive before it, older
+    /// archives stay the same. `feed_url` must not contain an extension yet.
+    ///
+    /// Returns the url suffix ("" for the subscription document, "-archive-<n>" for
+    /// archives) together with the feed document for format with `extension`.
+    pub fn get_pages(&self, max_entries: usize, extension: &str) -> Vec<(String, Feed)> {
+        let mut current = self.clone();
+        current.feed_url = format!("{}.{}", self.feed_url, extension);
+
+        // everything fits in the subscription document:
+        if max_entries == 0 || self.entries.len() <= max_entries {
+            return vec![(String::new(), current)];
+        }
+
+        let older_entries = &self.entries[max_entries..];
+        let num_archives = older_entries.len().div_ceil(max_entries);
+        let get_archive_url = |i: usize| format!("{}-archive-{}.{}", self.feed_url, i, extension);
+
+        current.entries.truncate(max_entries);
+        current.prev_archive_url = get_archive_url(num_archives);
+        let mut pages = vec![(String::new(), current)];
+
+        for i in 1..num_archives + 1 {
+            let end = older_entries.len() - max_entries * (i - 1);
+            let start = end.saturating_sub(max_entries);
+
+            let mut archive = self.clone();
+            archive.entries = older_entries[start..end].to_vec();
+            archive.is_archive = true;
+            archive.feed_url = get_archive_url(i);
+            archive.current_url = format!("{}.{}", self.feed_url, extension);
+            if i > 1 {
+                archive.prev_archive_url = get_archive_url(i - 1);
+            }
+            if i < num_archives {
+                archive.next_archive_url = get_archive_url(i + 1);
+            }
+
+            pages.push((format!("-archive-{}", i), archive));
+        }
+
+        return pages;
+    }
+
+    pub fn serialize(&self, format: FeedFormat) -> Result<String, SiteError> {
+        return match format {
+            FeedFormat::Atom => self.to_atom(),
+            FeedFormat::Rss => self.to_rss(),
+            FeedFormat::Json => Ok(self.to_json()),
+        };
+    }
+
+    /// Returns the newest `updated` date of all entries, so that rebuilding unchanged
+    /// content results in an identical feed.
+    pub fn get_updated(&self) -> DateTime<FixedOffset> {
+        return self
+            .entries
+            .iter()
+            .map(|entry| entry.updated)
+            .max()
+            .unwrap_or_default();
+    }
+
+    pub fn to_atom(&self) -> Result<String, SiteError> {
+        let mut xml = XmlWriter::new();
+
+        // header info:
+        let mut feed_attributes = vec![("xmlns", "http://www.w3.org/2005/Atom")];
+        if self.is_archive {
+            feed_attributes.push(("xmlns:fh", HISTORY_NAMESPACE));
+        }
+        xml.start("feed", &feed_attributes);
+        // archives are part of the same logical feed as the subscription document:
+        let feed_id = if self.is_archive {
+            &self.current_url
+        } else {
+            &self.feed_url
+        };
+        xml.element("id", &[], feed_id);
+        xml.element("title", &[], &self.title);
+        xml.element("updated", &[], &get_date_for_atom(&self.get_updated()));
+        xml.element("generator", &[], &self.generator);
+        xml.start("author", &[]);
+        xml.element("name", &[], &self.author);
+        xml.element("uri", &[], &self.site_url);
+        xml.end();
+        xml.empty(
+            "link",
+            &[("rel", "alternate"), ("href", &self.alternate_url)],
+        );
+        xml.empty("link", &[("rel", "self"), ("href", &self.feed_url)]);
+        self.write_archive_links(&mut xml, "link");
+        xml.element("subtitle", &[], &self.subtitle);
+        xml.element("logo", &[], &self.logo_url);
+        xml.element("icon", &[], &self.icon_url);
+
+        // each entry:
+        for entry in &self.entries {
+            xml.start("entry", &[]);
+
+            // author:
+            xml.start("author", &[]);
+            xml.element("name", &[], &entry.author);
+            xml.end();
+
+            // titles are plain text, unlike summary and content:
+            xml.element("title", &[("type", "text")], &entry.title);
+            xml.empty("link", &[("href", &entry.url)]);
+            xml.element("id", &[], &entry.url);
+            xml.element("updated", &[], &get_date_for_atom(&entry.updated));
+            xml.element("published", &[], &get_date_for_atom(&entry.published));
+
+            // categories:
+            for category in &entry.categories {
+                xml.empty("category", &[("term", category)]);
+            }
+
+            // summary and content (html is escaped as a whole):
+            xml.element("summary", &[("type", "html")], &entry.summary);
+            xml.element("content", &[("type", "html")], &entry.content);
+
+            xml.end();
+        }
+
+        // close feed:
+        xml.end();
+
+        return xml.finish();
+    }
+
+    pub fn to_rss(&self) -> Result<String, SiteError> {
+        let mut xml = XmlWriter::new();
+
+        // header info:
+        let mut rss_attributes = vec![
+            ("version", "2.0"),
+            ("xmlns:atom", "http://www.w3.org/2005/Atom"),
+            ("xmlns:dc", "http://purl.org/dc/elements/1.1/"),
+        ];
+        if self.is_archive {
+            rss_attributes.push(("xmlns:fh", HISTORY_NAMESPACE));
+        }
+        xml.start("rss", &rss_attributes);
+        xml.start("channel", &[]);
+        xml.element("title", &[], &self.title);
+        xml.element("link", &[], &self.alternate_url);
+        xml.element("description", &[], &self.subtitle);
+        xml.empty(
+            "atom:link",
+            &[
+                ("href", &self.feed_url),
+                ("rel", "self"),
+                ("type", "application/rss+xml"),
+            ],
+        );
+        self.write_archive_links(&mut xml, "atom:link");
+        xml.element("lastBuildDate", &[], &get_date_for_rss(&self.get_updated()));
+        xml.element("generator", &[], &self.generator);
+        xml.start("image", &[]);
+        xml.element("url", &[], &self.logo_url);
+        xml.element("title", &[], &self.title);
+        xml.element("link", &[], &self.alternate_url);
+        xml.end();
+
+        // each entry:
+        for entry in &self.entries {
+            xml.start("item", &[]);
+            xml.element("title", &[], &entry.title);
+            xml.element("link", &[], &entry.url);
+            xml.element("guid", &[("isPermaLink", "true")], &entry.url);
+            xml.element("pubDate", &[], &get_date_for_rss(&entry.published));
+            xml.element("dc:creator", &[], &entry.author);
+            for category in &entry.categories {
+                xml.element("category", &[], category);
+            }
+            xml.element("description", &[], &entry.content);
+            xml.end();
+        }
+
+        // close feed:
+        xml.end();
+        xml.end();
+
+        return xml.finish();
+    }
+
+    /// Writes RFC 5005 archive links, `link_name` is the name of the Atom link element
+    /// ("link" in Atom and "atom:link" in RSS).
+    fn write_archive_links(&self, xml: &mut XmlWriter, link_name: &str) {
+        if self.is_archive {
+            xml.empty("fh:archive", &[]);
+        }
+        if !self.current_url.is_empty() {
+            xml.empty(
+                link_name,
+                &[("rel", "current"), ("href", &self.current_url)],
+            );
+        }
+        if !self.prev_archive_url.is_empty() {
+            xml.empty(
+                link_name,
+                &[("rel", "prev-archive"), ("href", &self.prev_archive_url)],
+            );
+        }
+        if !self.next_archive_url.is_empty() {
+            xml.empty(
+                link_name,
+                &[("rel", "next-archive"), ("href", &self.next_archive_url)],
+            );
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // header info:
+        let mut feed_data = String::from("{\n");
+        feed_data += "    \"version\": \"https://jsonfeed.org/version/1.1\",\n";
+        feed_data += &format!("    \"title\": {},\n", get_json_string(&self.title));
+        feed_data += &format!(
+            "    \"home_page_url\": {},\n",
+            get_json_string(&self.alternate_url)
+        );
+        feed_data += &format!("    \"feed_url\": {},\n", get_json_string(&self.feed_url));
+        // JSON Feed pages through older items with next_url:
+        if !self.prev_archive_url.is_empty() {
+            feed_data += &format!(
+                "    \"next_url\": {},\n",
+                get_json_string(&self.prev_archive_url)
+            );
+        }
+        feed_data += &format!(
+            "    \"description\": {},\n",
+            get_json_string(&self.subtitle)
+        );
+        feed_data += &format!("    \"icon\": {},\n", get_json_string(&self.logo_url));
+        feed_data += &format!("    \"favicon\": {},\n", get_json_string(&self.icon_url));
+        feed_data += &format!(
+            "    \"authors\": [{{ \"name\": {}, \"url\": {} }}],\n",
+            get_json_string(&self.author),
+            get_json_string(&self.site_url)
+        );
+        feed_data += "    \"items\": [";
+
+        // each entry:
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                feed_data += ",";
+            }
+            feed_data += "\n        {\n";
+            feed_data += &format!("            \"id\": {},\n", get_json_string(&entry.url));
+            feed_data += &format!("            \"url\": {},\n", get_json_string(&entry.url));
+            feed_data += &format!(
+                "            \"title\": {},\n",
+                get_json_string(&entry.title)
+            );
+            feed_data += &format!(
+                "            \"summary\": {},\n",
+                get_json_string(&entry.summary)
+            );
+            feed_data += &format!(
+                "            \"content_html\": {},\n",
+                get_json_string(&entry.content)
+            );
+            feed_data += &format!(
+                "            \"date_published\": {},\n",
+                get_json_string(&get_date_for_atom(&entry.published))
+            );
+            feed_data += &format!(
+                "            \"date_modified\": {},\n",
+                get_json_string(&get_date_for_atom(&entry.updated))
+            );
+            feed_data += &format!(
+                "            \"authors\": [{{ \"name\": {} }}],\n",
+                get_json_string(&entry.author)
+            );
+            let tags: Vec<String> = entry
+                .categories
+                .iter()
+                .map(|category| get_json_string(category))
+                .collect();
+            feed_data += &format!("            \"tags\": [{}]\n", tags.join(", "));
+            feed_data += "        }";
+        }
+
+        // close feed:
+        feed_data += "\n    ]\n";
+        feed_data += "}";
+
+        return feed_data;
+    }
+}
+
+/// Returns `str` as a quoted JSON string with all special characters escaped.
+pub fn get_json_string(str: &str) -> String {
+    let mut json = String::with_capacity(str.len() + 2);
+    json.push('"');
+    for c in str.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            '\n' => json += "\\n",
+            '\r' => json += "\\r",
+            '\t' => json += "\\t",
+            c if (c as u32) < 0x20 => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+
+    return json;
+}
+
+/// RFC 3339 date as used by Atom and JSON Feed, e.g. "2022-01-13T16:24:00Z".
+fn get_date_for_atom(date: &DateTime<FixedOffset>) -> String {
+    return date.to_rfc3339_opts(SecondsFormat::Secs, true);
+}
+
+/// RFC 2822 date as used by RSS, e.g. "Thu, 13 Jan 2022 16:24:00 +0000".
+fn get_date_for_rss(date: &DateTime<FixedOffset>) -> String {
+    return date.to_rfc2822();
+}
@@ -0,0 +1,244 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use sailfish::Template;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::site::build_cache::BuildCache;
+use crate::site::error::SiteError;
+use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
+use crate::site::screenshot::Screenshot;
+use crate::site::sitemap::SitemapShared;
+use crate::site::template::Value;
+use crate::site::theme::{Theme, ThemeTemplate};
+
+/// Screenshots read from one file in 'screenshots', with an index page at its url.
+#[derive(Template)]
+#[template(path = "screenshots.stpl")]
+#[derive(Clone, Debug)]
+pub struct Gallery {
+    pub source_path: PathBuf,
+
+    pub title: String,
+    pub url: String,
+    pub screenshots: Vec<Screenshot>,
+    pub menu: Arc<Menu>,
+}
+
+impl Gallery {
+    pub fn new() -> Self {
+        Self {
+            source_path: PathBuf::new(),
+
+            title: String::new(),
+            url: String::new(),
+            screenshots: Vec::new(),
+            menu: Arc::new(Menu::new()),
+        }
+    }
+
+    /// Sets the menu shown on the pages of the gallery and links every screenshot to the
+    /// others, as they are listed below it.
+    pub fn set_menu(&mut self, menu: Arc<Menu>) {
+        self.menu = menu.clone();
+        for screenshot in &mut self.screenshots {
+            screenshot.menu = menu.clone();
+        }
+
+        let screenshots = self.screenshots.clone();
+        for screenshot in &mut self.screenshots {
+            screenshot.screenshots = screenshots.clone();
+        }
+    }
+
+    pub fn get_output_file(&self, paths: &SitePaths) -> PathBuf {
+        return paths.output_dir.join(&self.url).join("index.html");
+    }
+
+    /// Fields shown where this gallery is listed, for runtime templates.
+    pub fn get_summary_context(&self) -> Value {
+        return Value::map(vec![
+            ("title", Value::from(&self.title)),
+            ("url", Value::from(&self.url)),
+            ("num_screenshots", Value::from(self.screenshots.len())),
+            (
+                "cover",
+                match self.screenshots.first() {
+                    Some(screenshot) => screenshot.get_summary_context(),
+                    _ => Value::Null,
+                },
+            ),
+        ]);
+    }
+
+    /// Writes the index of the gallery and a page for every screenshot, unless the file
+    /// of the gallery is unchanged since the last build.
+    pub fn generate(
+        &self,
+        paths: &SitePaths,
+        sitemap: &SitemapShared,
+        cache: &BuildCache,
+        theme: &Theme,
+    ) -> Result<(), SiteError> {
+        let source = std::fs::read(&self.source_path)
+            .map_err(|e| SiteError::io("read", &self.source_path, e))?;
+        let is_changed = cache.is_source_changed(&self.source_path, &source);
+
+        let index_file = self.get_output_file(paths);
+        if is_changed || !cache.keep_file(&index_file) {
+            // create output dir needed:
+            Helper::create_dir_all(&paths.output_dir.join(&self.url))?;
+
+            // write page to disk:
+            let html = theme.render(self, &index_file)?;
+            cache.write_file_sync(&index_file, html.as_bytes())?;
+        }
+        sitemap.add_page(&self.url, None);
+
+        // generate all individual screenshot pages:
+        for screenshot in &self.screenshots {
+            if is_changed || !cache.keep_file(&screenshot.get_output_file(paths)) {
+                screenshot.generate(paths, cache, theme)?;
+            }
+            sitemap.add_page(&screenshot.url, None);
+        }
+
+        Ok(())
+    }
+}
+
+impl ThemeTemplate for Gallery {
+    fn get_template_name(&self) -> &str {
+        return "screenshots.html";
+    }
+
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
+
+    fn get_context(&self) -> Value {
+        let mut context = self.get_summary_context();
+        context.insert(
+            "canonical_url",
+            Value::from(Helper::get_absolute_url(&self.url)),
+        );
+        context.insert(
+            "screenshots",
+            Value::from(
+                self.screenshots
+                    .iter()
+                    .map(|screenshot| screenshot.get_summary_context())
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+
+        return context;
+    }
+}
+
+pub async fn parse_gallery_file(path: PathBuf) -> Result<Gallery, SiteError> {
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| SiteError::io("read", &path, e))?;
+    let mut gallery = Gallery::new();
+    gallery.source_path = path.clone();
+    let mut screenshot = Screenshot::new(gallery.menu.clone());
+
+    for (i, line) in contents.lines().enumerate() {
+        let error = |message: &str| SiteError::parse(&path, Some(i + 1), message);
+        if line.is_empty() {
+            continue;
+        }
+
+        let v: Vec<&str> = line.splitn(2, ':').collect();
+        if v.len() != 2 {
+            return Err(error("Expected a line like 'key: value'."));
+        }
+
+        if let Some(key) = v.first() {
+            // one gallery per file, its title and url come before the screenshots:
+            if (*key == "screenshots_title" || *key == "screenshots_url")
+                && !gallery.screenshots.is_empty()
+            {
+                return Err(error(&format!(
+                    "Unexpected field: '{}', use one file per gallery.",
+                    key
+                )));
+            }
+
+            if *key == "screenshots_title" {
+                if let Some(value) = v.get(1) {
+                    gallery.title = String::from(value.trim());
+                } else {
+                    return Err(error("Unable to parse field: 'screenshots_title'."));
+                }
+            } else if *key == "screenshots_url" {
+                if let Some(value) = v.get(1) {
+                    gallery.url = String::from(value.trim().trim_matches('/'));
+                } else {
+                    return Err(error("Unable to parse field: 'screenshots_url'."));
+                }
+            } else if *key == "title" {
+                if let Some(value) = v.get(1) {
+                    screenshot.title = String::from(value.trim());
+                } else {
+                    return Err(error("Unable to parse field: 'title'."));
+                }
+            } else if *key == "image_min" {
+                if let Some(value) = v.get(1) {
+                    screenshot.image_min = String::from(value.trim());
+                } else {
+                    return Err(error("Unable to parse field: 'image_min'."));
+                }
+            } else if *key == "image_big" {
+                if let Some(value) = v.get(1) {
+                    screenshot.image_big = String::from(value.trim());
+                } else {
+                    return Err(error("Unable to parse field: 'image_big'."));
+                }
+            } else if *key == "url" {
+                if let Some(value) = v.get(1) {
+                    screenshot.url = String::from(value.trim());
+                } else {
+                    return Err(error("Unable to parse field: 'url'."));
+                }
+            }
+        }
+
+        // all info needed for one screenshot:
+        if !screenshot.title.is_empty()
+            && !screenshot.image_min.is_empty()
+            && !screenshot.image_big.is_empty()
+            && !screenshot.url.is_empty()
+            && !gallery.title.is_empty()
+            && !gallery.url.is_empty()
+        {
+            screenshot.screenshots_title = gallery.title.clone();
+            screenshot.screenshots_url = gallery.url.clone();
+            gallery.screenshots.push(screenshot);
+
+            // reset for new screenshot:
+            screenshot = Screenshot::new(gallery.menu.clone());
+        }
+    }
+
+    if gallery.title.is_empty() {
+        return Err(SiteError::parse(
+            &path,
+            None,
+            "Missing field: 'screenshots_title'.",
+        ));
+    }
+    if gallery.url.is_empty() {
+        return Err(SiteError::parse(
+            &path,
+            None,
+            "Missing field: 'screenshots_url'.",
+        ));
+    }
+
+    return Ok(gallery);
+}
@@ -1,29 +1,78 @@
 // luflow.net web site
 // AGPL-3.0 License (see LICENSE)
 
+use chrono::{DateTime, FixedOffset, SecondsFormat};
 use regex::Regex;
-use std::env;
 use std::fs;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU8, Ordering};
 use tokio::fs::File;
 use tokio::io::{self, AsyncWriteExt};
+use tokio::task::JoinHandle;
+
+use crate::site::config::SITE_URL;
+use crate::site::error::SiteError;
+
+/// How much is printed while generating, set once from the command line options.
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verbosity {
+    /// Only errors.
+    Quiet = 0,
+    /// Written, copied and removed files.
+    Normal = 1,
+    /// Everything, including created dirs and unchanged files.
+    Verbose = 2,
+}
 
 pub struct Helper {}
 
 impl Helper {
-    pub fn get_current_working_dir() -> PathBuf {
-        let res = env::current_dir();
-        match res {
-            Ok(path) => path,
-            Err(_) => PathBuf::new(),
+    pub fn set_verbosity(verbosity: Verbosity) {
+        VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+    }
+
+    /// Prints `message` if the verbosity given on the command line is at least
+    /// `verbosity`.
+    pub fn print(verbosity: Verbosity, message: &str) {
+        if VERBOSITY.load(Ordering::Relaxed) >= verbosity as u8 {
+            println!("{}", message);
+        }
+    }
+
+    /// Returns the absolute url of the page at `path` (relative to the output dir, "" for
+    /// the root index), e.g. "https://www.luflow.net/blog/".
+    pub fn get_absolute_url(path: &str) -> String {
+        if path.is_empty() {
+            return format!("{}/", SITE_URL);
         }
+
+        // files like '404.html' don't get a trailing '/':
+        if path.ends_with(".html") {
+            return format!("{}/{}", SITE_URL, path);
+        }
+
+        return format!("{}/{}/", SITE_URL, path);
+    }
+
+    /// Formats `date` for display with the `date_format` from 'site.conf'.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let date = DateTime::parse_from_rfc3339("2022-01-13T16:24:00+01:00").unwrap();
+    /// assert_eq!(Helper::format_date(&date, "%-d %B %Y"), "13 January 2022");
+    /// ```
+    pub fn format_date(date: &DateTime<FixedOffset>, date_format: &str) -> String {
+        return date.format(date_format).to_string();
     }
 
-    pub fn get_output_dir() -> PathBuf {
-        let cwd = Helper::get_current_working_dir();
-        return cwd.join("output");
+    /// Machine readable `date` for the `datetime` attribute of `<time>` elements.
+    pub fn get_date_for_html(date: &DateTime<FixedOffset>) -> String {
+        return date.to_rfc3339_opts(SecondsFormat::Secs, true);
     }
 
     /// Replaces spaces with '-', only allows 'a-z', 'A-Z', '0-9' and '-' characters and finally
@@ -53,70 +102,185 @@ impl Helper {
         return after_unsupported.to_string().to_lowercase();
     }
 
-    pub fn create_dir_all<'a>(dir: &'a PathBuf) {
-        match fs::create_dir_all(dir) {
-            Ok(()) => println!("Created dir: '{}'", dir.display()),
-            Err(err) => panic!(
-                "Failed to create dir: '{}'. Error msg: '{}'",
-                dir.display(),
-                err
-            ),
-        };
+    /// Returns the trimmed '/' separated parts of a path that are left after sanitizing, the
+    /// parts `sanitize_path` keeps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let parts = Helper::get_path_parts("Hardware / ?/ThinkPad X230");
+    /// assert_eq!(parts, vec!["Hardware", "ThinkPad X230"]);
+    /// ```
+    pub fn get_path_parts(str: &str) -> Vec<&str> {
+        let mut parts = Vec::new();
+        for part in str.split('/') {
+            let part_trimmed = part.trim();
+            if !Helper::sanitize_string(part_trimmed).is_empty() {
+                parts.push(part_trimmed);
+            }
+        }
+
+        return parts;
+    }
+
+    /// Sanitizes each '/' separated part of a path with `sanitize_string` and joins them again
+    /// with '/'. Parts that are empty after sanitizing are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `str` - is the path to sanitize.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let str_to_sanitize = "Hardware / Coreboot/ThinkPad X230";
+    /// let str_sanitized = Helper::sanitize_path(str_to_sanitize);
+    /// assert_eq!(str_sanitized, "hardware/coreboot/thinkpad-x230");
+    /// ```
+    pub fn sanitize_path(str: &str) -> String {
+        return Helper::get_path_parts(str)
+            .into_iter()
+            .map(Helper::sanitize_string)
+            .collect::<Vec<String>>()
+            .join("/");
+    }
+
+    pub fn create_dir_all(dir: &PathBuf) -> Result<(), SiteError> {
+        fs::create_dir_all(dir).map_err(|e| SiteError::io("create dir", dir, e))?;
+        Helper::print(
+            Verbosity::Verbose,
+            &format!("Created dir: '{}'", dir.display()),
+        );
+
+        Ok(())
+    }
+
+    pub fn remove_dir_all(dir: &PathBuf) -> Result<(), SiteError> {
+        fs::remove_dir_all(dir).map_err(|e| SiteError::io("remove dir", dir, e))?;
+        Helper::print(
+            Verbosity::Normal,
+            &format!("Removed dir: '{}'", dir.display()),
+        );
+
+        Ok(())
+    }
+
+    pub fn rename_dir(from: &PathBuf, to: &PathBuf) -> Result<(), SiteError> {
+        fs::rename(from, to).map_err(|e| SiteError::io("rename dir", from, e))?;
+
+        Ok(())
+    }
+
+    /// Returns `path` relative to `dir`, e.g. for files found with `get_files_recursive`.
+    pub fn get_relative_path<'a>(path: &'a Path, dir: &Path) -> Result<&'a Path, SiteError> {
+        return path.strip_prefix(dir).map_err(|_| {
+            SiteError::content(&format!(
+                "Path: '{}' is not in '{}'.",
+                path.display(),
+                dir.display()
+            ))
+        });
+    }
+
+    /// Hard links all files in `src` recursively into `dst`, files are copied where
+    /// linking is not supported (e.g. across file systems).
+    pub fn link_dir_all(src: &Path, dst: &Path) -> Result<(), SiteError> {
+        for src_file in Helper::get_files_recursive(src)? {
+            let dst_file = dst.join(Helper::get_relative_path(&src_file, src)?);
+            if let Some(parent) = dst_file.parent() {
+                fs::create_dir_all(parent).map_err(|e| SiteError::io("create dir", parent, e))?;
+            }
+
+            if fs::hard_link(&src_file, &dst_file).is_err() {
+                fs::copy(&src_file, &dst_file)
+                    .map_err(|e| SiteError::io("copy file", &src_file, e))?;
+            }
+        }
+
+        Ok(())
     }
 
-    pub fn remove_dir_all<'a>(dir: &'a PathBuf) {
-        match fs::remove_dir_all(dir) {
-            Ok(()) => println!("Removed dir: '{}'", dir.display()),
-            Err(err) => panic!(
-                "Failed to remove dir: '{}'. Error msg: '{}'",
-                dir.display(),
-                err
-            ),
+    /// Waits until every task of `tasks` is done, even if one failed before, so none of
+    /// them writes to the output anymore. Returns their results in order or the first
+    /// error.
+    pub async fn join_all<T>(
+        tasks: Vec<JoinHandle<Result<T, SiteError>>>,
+    ) -> Result<Vec<T>, SiteError> {
+        let mut values = Vec::with_capacity(tasks.len());
+        let mut first_error = None;
+        for task in tasks {
+            let error = match task.await {
+                Ok(Ok(value)) => {
+                    values.push(value);
+                    continue;
+                }
+                Ok(Err(e)) => e,
+                Err(e) => SiteError::from(e),
+            };
+            if first_error.is_none() {
+                first_error = Some(error);
+            }
+        }
+
+        return match first_error {
+            Some(e) => Err(e),
+            _ => Ok(values),
         };
     }
 
-    pub fn exists_dir<'a>(dir: &'a PathBuf) -> bool {
-        return dir.as_path().exists();
+    pub fn exists_dir(dir: &Path) -> bool {
+        return dir.exists();
     }
 
-    #[async_recursion::async_recursion]
-    pub async fn copy_dir_all<S, D>(src: S, dst: D) -> Result<(), std::io::Error>
-    where
-        S: AsRef<Path> + Send + Sync,
-        D: AsRef<Path> + Send + Sync,
-    {
-        tokio::fs::create_dir_all(&dst).await?;
-        let mut entries = tokio::fs::read_dir(src).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            let ty = entry.file_type().await?;
-            if ty.is_dir() {
-                Self::copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name())).await?;
+    pub fn get_files_recursive(dir: &Path) -> Result<Vec<PathBuf>, SiteError> {
+        let mut files = Vec::new();
+        let entries = fs::read_dir(dir).map_err(|e| SiteError::io("read dir", dir, e))?;
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                files.append(&mut Helper::get_files_recursive(&path)?);
             } else {
-                tokio::fs::copy(entry.path(), dst.as_ref().join(entry.file_name())).await?;
+                files.push(path);
             }
         }
-        Ok(())
+
+        return Ok(files);
     }
 
     pub async fn write_file<'a>(file_path: &'a PathBuf, data: &'a [u8]) -> io::Result<()> {
+        // outputs may be hard linked to the published site, replace instead of writing
+        // through the link:
+        let _ = tokio::fs::remove_file(file_path).await;
+
         // create output file:
         let mut file = File::create(file_path).await?;
 
         // write data to file:
         file.write_all(data).await?;
 
-        println!("Wrote '{}' successfully", file_path.display());
+        Helper::print(
+            Verbosity::Normal,
+            &format!("Wrote '{}' successfully", file_path.display()),
+        );
         Ok(())
     }
 
     pub fn write_file_sync<'a>(file_path: &'a PathBuf, data: &'a [u8]) -> io::Result<()> {
+        // outputs may be hard linked to the published site, replace instead of writing
+        // through the link:
+        let _ = fs::remove_file(file_path);
+
         // create output file:
         let mut file = std::fs::File::create(file_path)?;
 
         // write data to file:
         file.write_all(data)?;
 
-        println!("Wrote '{}' successfully", file_path.display());
+        Helper::print(
+            Verbosity::Normal,
+            &format!("Wrote '{}' successfully", file_path.display()),
+        );
         Ok(())
     }
 }
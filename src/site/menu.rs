@@ -0,0 +1,126 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use crate::site::feed::FeedFormat;
+use crate::site::gallery::Gallery;
+use crate::site::page::Page;
+use crate::site::project::Project;
+use crate::site::template::Value;
+
+#[derive(Clone, Debug)]
+pub struct MenuEntry {
+    pub title: String,
+    pub url: String,
+}
+
+/// Entries of the header menu that come from the site sources, shown on every page,
+/// together with the formats of the blog feed every page links to.
+#[derive(Clone, Debug)]
+pub struct Menu {
+    pub projects: Vec<MenuEntry>,
+    pub galleries: Vec<MenuEntry>,
+    pub pages: Vec<MenuEntry>,
+    pub feed_formats: Vec<FeedFormat>,
+}
+
+impl Menu {
+    pub fn new() -> Self {
+        Self {
+            projects: Vec::new(),
+            galleries: Vec::new(),
+            pages: Vec::new(),
+            feed_formats: Vec::new(),
+        }
+    }
+
+    /// Menu with an entry for every project, every gallery (named as the project that
+    /// shows it, if any) and every page that sets 'menu' in its front matter.
+    pub fn from_sources(
+        projects: &[Project],
+        galleries: &[Gallery],
+        pages: &[Page],
+        feed_formats: &[FeedFormat],
+    ) -> Self {
+        let mut menu = Self::new();
+        menu.feed_formats = feed_formats.to_vec();
+        for project in projects {
+            menu.projects.push(MenuEntry {
+                title: project.name.clone(),
+                url: format!("/{}/", project.url),
+            });
+        }
+        for gallery in galleries {
+            let title = match projects
+                .iter()
+                .find(|project| project.gallery == gallery.url)
+            {
+                Some(project) => project.name.clone(),
+                _ => gallery.title.clone(),
+            };
+            menu.galleries.push(MenuEntry {
+                title,
+                url: format!("/{}/", gallery.url),
+            });
+        }
+        for page in pages {
+            if !page.menu_title.is_empty() {
+                menu.pages.push(MenuEntry {
+                    title: page.menu_title.clone(),
+                    url: page.get_link(),
+                });
+            }
+        }
+
+        return menu;
+    }
+
+    /// The menu is part of every page, all pages are rendered again when it changes.
+    pub fn get_build_data(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for entry in self
+            .projects
+            .iter()
+            .chain(&self.galleries)
+            .chain(&self.pages)
+        {
+            data.extend_from_slice(entry.title.as_bytes());
+            data.push(b'\n');
+            data.extend_from_slice(entry.url.as_bytes());
+            data.push(b'\n');
+        }
+
+        return data;
+    }
+
+    /// Fields of the menu, for runtime templates.
+    pub fn get_context(&self) -> Value {
+        return Value::map(vec![
+            ("projects", get_entries_context(&self.projects)),
+            ("galleries", get_entries_context(&self.galleries)),
+            ("pages", get_entries_context(&self.pages)),
+            (
+                "feed_formats",
+                Value::from(
+                    self.feed_formats
+                        .iter()
+                        .map(|format| format.get_context())
+                        .collect::<Vec<Value>>(),
+                ),
+            ),
+        ]);
+    }
+}
+
+fn get_entries_context(entries: &[MenuEntry]) -> Value {
+    return Value::from(
+        entries
+            .iter()
+            .map(|entry| {
+                Value::map(vec![
+                    ("title", Value::from(&entry.title)),
+                    ("url", Value::from(&entry.url)),
+                ])
+            })
+            .collect::<Vec<Value>>(),
+    );
+}
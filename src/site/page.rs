@@ -0,0 +1,401 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use sailfish::Template;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::site::build_cache::BuildCache;
+use crate::site::error::SiteError;
+use crate::site::gallery::Gallery;
+use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
+use crate::site::project::Project;
+use crate::site::projects::BASE_DIR as PROJECTS_DIR;
+use crate::site::screenshots::BASE_DIR as SCREENSHOTS_DIR;
+use crate::site::template::Value;
+use crate::site::theme::{Theme, ThemeTemplate, get_layout_template_name};
+
+/// Layout of pages that don't set one.
+const DEFAULT_LAYOUT: &str = "page";
+
+/// Pages with this layout are left out of the sitemap and get no canonical link.
+const ERROR_LAYOUT: &str = "error";
+
+/// Dirs in the output dir that are generated as a whole, besides the blog.
+const GENERATED_DIRS: [&str; 3] = ["feeds", "search", "static"];
+
+/// Files generated in the root of the output dir.
+const GENERATED_FILES: [&str; 3] = ["index.html", "sitemap.xml", "robots.txt"];
+
+/// A page made from a markdown file in 'pages', e.g. 'pages/contact.md'.
+#[derive(Template)]
+#[template(path = "page.stpl")]
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub source_path: PathBuf,
+
+    pub title: String,
+    pub layout: String,
+    pub url: String,
+    pub menu_title: String,
+    pub description: String,
+    pub keywords: String,
+    pub html: String,
+    pub menu: Arc<Menu>,
+    template_name: String,
+}
+
+impl Page {
+    pub fn new() -> Self {
+        Self {
+            source_path: PathBuf::new(),
+
+            title: String::new(),
+            layout: String::from(DEFAULT_LAYOUT),
+            url: String::new(),
+            menu_title: String::new(),
+            description: String::new(),
+            keywords: String::new(),
+            html: String::new(),
+            menu: Arc::new(Menu::new()),
+            template_name: get_layout_template_name(DEFAULT_LAYOUT),
+        }
+    }
+
+    /// A url ending in '.html' is written as that file (e.g. '404.html'), any other
+    /// url as 'index.html' in a dir of that name.
+    pub fn get_output_file(&self, paths: &SitePaths) -> PathBuf {
+        return paths.output_dir.join(self.get_output_path());
+    }
+
+    /// Path of the output file relative to the output dir, see `get_output_file`.
+    fn get_output_path(&self) -> String {
+        if self.url.ends_with(".html") {
+            return self.url.clone();
+        }
+
+        return format!("{}/index.html", self.url);
+    }
+
+    /// Link to the page from within the site, e.g. '/contact/'.
+    pub fn get_link(&self) -> String {
+        if self.url.ends_with(".html") {
+            return format!("/{}", self.url);
+        }
+
+        return format!("/{}/", self.url);
+    }
+
+    /// Error pages are only shown instead of missing or broken pages, they aren't listed.
+    pub fn is_listed(&self) -> bool {
+        return self.layout != ERROR_LAYOUT;
+    }
+
+    pub async fn generate(
+        &self,
+        paths: &SitePaths,
+        cache: &BuildCache,
+        theme: &Theme,
+    ) -> Result<(), SiteError> {
+        if !theme.has_layout(&self.layout) {
+            return Err(SiteError::parse(
+                &self.source_path,
+                None,
+                &format!("Did not find layout: '{}'.", self.layout),
+            ));
+        }
+
+        // create output dir needed:
+        let output_file = self.get_output_file(paths);
+        if let Some(parent) = output_file.parent() {
+            Helper::create_dir_all(&parent.to_path_buf())?;
+        }
+
+        // write page to disk:
+        let html = theme.render(self, &output_file)?;
+
+        return cache.write_file(&output_file, html.as_bytes()).await;
+    }
+}
+
+impl ThemeTemplate for Page {
+    fn get_template_name(&self) -> &str {
+        return &self.template_name;
+    }
+
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
+
+    fn get_context(&self) -> Value {
+        return Value::map(vec![
+            ("title", Value::from(&self.title)),
+            ("layout", Value::from(&self.layout)),
+            ("url", Value::from(&self.url)),
+            ("menu_title", Value::from(&self.menu_title)),
+            ("description", Value::from(&self.description)),
+            ("keywords", Value::from(&self.keywords)),
+            (
+                "canonical_url",
+                Value::from(Helper::get_absolute_url(&self.url)),
+            ),
+            ("html", Value::from(&self.html)),
+        ]);
+    }
+}
+
+/// Reads all pages in 'pages' of the site at `paths`, ordered by file name (which is
+/// also the order of their menu entries). Pages can't use the url of the blog at
+/// `blog_base_dir` (or below), of `projects`, `galleries` or their overviews, of the
+/// generated root files or of files in 'static_root'.
+pub async fn parse_pages(
+    paths: &SitePaths,
+    blog_base_dir: &str,
+    projects: &[Project],
+    galleries: &[Gallery],
+) -> Result<Vec<Page>, SiteError> {
+    let pages_dir = paths.get_pages_dir();
+    let mut pages: Vec<Page> = Vec::new();
+
+    // pages are optional:
+    if !Helper::exists_dir(&pages_dir) {
+        return Ok(pages);
+    }
+
+    // files copied to the root of the output dir, relative to it:
+    let mut static_root_files = Vec::new();
+    let static_root_dir = paths.get_static_root_dir();
+    if Helper::exists_dir(&static_root_dir) {
+        for file in Helper::get_files_recursive(&static_root_dir)? {
+            let relative_path = Helper::get_relative_path(&file, &static_root_dir)?;
+            static_root_files.push(relative_path.to_string_lossy().replace('\\', "/"));
+        }
+    }
+
+    let mut files = Helper::get_files_recursive(&pages_dir)?;
+    files.sort();
+    for path in files {
+        if path.extension().is_none_or(|extension| extension != "md") {
+            continue;
+        }
+
+        let page = parse_page_file(path, &pages_dir).await?;
+        if let Some(used_by) = get_static_root_used_by(&page, &static_root_files) {
+            return Err(SiteError::parse(
+                &page.source_path,
+                None,
+                &format!("Page url: '{}' is already used by {}.", page.url, used_by),
+            ));
+        }
+        if let Some(used_by) = get_generated_used_by(&page.url, blog_base_dir, projects, galleries)
+        {
+            return Err(SiteError::parse(
+                &page.source_path,
+                None,
+                &format!("Page url: '{}' is already used by {}.", page.url, used_by),
+            ));
+        }
+        if let Some(other) = pages.iter().find(|other| other.url == page.url) {
+            return Err(SiteError::parse(
+                &page.source_path,
+                None,
+                &format!(
+                    "Page url: '{}' is already used by '{}'.",
+                    page.url,
+                    other.source_path.display()
+                ),
+            ));
+        }
+        pages.push(page);
+    }
+
+    return Ok(pages);
+}
+
+/// Returns what else is generated at `url` (e.g. "the blog"), if anything.
+fn get_generated_used_by(
+    url: &str,
+    blog_base_dir: &str,
+    projects: &[Project],
+    galleries: &[Gallery],
+) -> Option<String> {
+    let is_in_dir = |dir: &str| url == dir || url.starts_with(&format!("{}/", dir));
+    if GENERATED_FILES.contains(&url) {
+        return Some(format!("the generated '{}'", url));
+    }
+    if is_in_dir(blog_base_dir) {
+        return Some(String::from("the blog"));
+    }
+    if let Some(dir) = GENERATED_DIRS.iter().find(|dir| is_in_dir(dir)) {
+        return Some(format!("the generated '{}' dir", dir));
+    }
+    if url == PROJECTS_DIR {
+        return Some(String::from("the overview of all projects"));
+    }
+    if url == SCREENSHOTS_DIR {
+        return Some(String::from("the overview of all galleries"));
+    }
+
+    if let Some(project) = projects.iter().find(|project| project.url == url) {
+        return Some(format!("'{}'", project.source_path.display()));
+    }
+    for gallery in galleries {
+        if gallery.url == url
+            || gallery
+                .screenshots
+                .iter()
+                .any(|screenshot| screenshot.url == url)
+        {
+            return Some(format!("'{}'", gallery.source_path.display()));
+        }
+    }
+
+    return None;
+}
+
+/// Returns the file in 'static_root' that is written where `page` is, or where it needs
+/// a dir, if any.
+fn get_static_root_used_by(page: &Page, static_root_files: &[String]) -> Option<String> {
+    let output_path = page.get_output_path();
+    for file in static_root_files {
+        if output_path == *file || output_path.starts_with(&format!("{}/", file)) {
+            return Some(format!("'static_root/{}'", file));
+        }
+    }
+
+    return None;
+}
+
+pub async fn parse_page_file(path: PathBuf, pages_dir: &Path) -> Result<Page, SiteError> {
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| SiteError::io("read", &path, e))?;
+    let mut body_start_found = false;
+    let mut markdown = String::new();
+    let mut page = Page::new();
+    page.source_path = path.clone();
+
+    // the file path (without extension) relative to 'pages' is the default url, e.g.
+    // 'pages/projects/about.md' is at '/projects/about/':
+    page.url = match path.strip_prefix(pages_dir) {
+        Ok(value) => Helper::sanitize_path(&value.with_extension("").to_string_lossy()),
+        _ => {
+            return Err(SiteError::parse(
+                &path,
+                None,
+                "Unable to get page url from path.",
+            ));
+        }
+    };
+
+    for (i, line) in contents.lines().enumerate() {
+        let error = |message: &str| SiteError::parse(&path, Some(i + 1), message);
+        if !body_start_found && !line.is_empty() {
+            if line == "---" {
+                body_start_found = true;
+                continue;
+            }
+
+            let v: Vec<&str> = line.splitn(2, ':').collect();
+            if v.len() != 2 {
+                return Err(error("Expected a line like 'key: value'."));
+            }
+
+            if let Some(key) = v.first() {
+                if *key == "title" {
+                    if let Some(value) = v.get(1) {
+                        page.title = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'title'."));
+                    }
+                } else if *key == "layout" {
+                    match v.get(1).map(|value| value.trim()) {
+                        Some(value)
+                            if !value.is_empty() && value == Helper::sanitize_string(value) =>
+                        {
+                            page.layout = String::from(value);
+                            page.template_name = get_layout_template_name(value);
+                        }
+                        _ => return Err(error("Unable to parse field: 'layout'.")),
+                    }
+                } else if *key == "url" {
+                    // a path below the output dir, leading and trailing '/' are optional:
+                    match v.get(1).map(|value| value.trim().trim_matches('/')) {
+                        Some(value)
+                            if !value.is_empty()
+                                && !value
+                                    .split('/')
+                                    .any(|part| part.is_empty() || part == "." || part == "..") =>
+                        {
+                            // other files (e.g. 'robots.txt') can't be written by a page:
+                            if value.trim_end_matches(".html").contains('.') {
+                                return Err(error(
+                                    "Only a url ending in '.html' may contain a '.'.",
+                                ));
+                            }
+                            page.url = get_sanitized_url(value);
+                            if page.url.is_empty() || page.url.starts_with(".html") {
+                                return Err(error("Unable to parse field: 'url'."));
+                            }
+                        }
+                        _ => return Err(error("Unable to parse field: 'url'.")),
+                    }
+                } else if *key == "menu" {
+                    if let Some(value) = v.get(1) {
+                        page.menu_title = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'menu'."));
+                    }
+                } else if *key == "description" {
+                    if let Some(value) = v.get(1) {
+                        page.description = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'description'."));
+                    }
+                } else if *key == "keywords" {
+                    if let Some(value) = v.get(1) {
+                        page.keywords = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'keywords'."));
+                    }
+                }
+            }
+        } else {
+            if !markdown.is_empty() {
+                markdown += "\n";
+            }
+            markdown += line;
+        }
+    }
+
+    if page.title.is_empty() {
+        return Err(SiteError::parse(&path, None, "Missing field: 'title'."));
+    }
+
+    // convert markdown to html, pages are written by the site owner so they may
+    // contain html too:
+    let options = markdown::Options {
+        compile: markdown::CompileOptions {
+            allow_dangerous_html: true,
+            ..markdown::CompileOptions::default()
+        },
+        ..markdown::Options::default()
+    };
+    page.html = markdown::to_html_with_options(&markdown, &options)
+        .map_err(|e| SiteError::parse(&path, None, &e.to_string()))?;
+
+    return Ok(page);
+}
+
+/// Sanitizes each part of the `url` given in the front matter like the default url of a
+/// page, only the extension of a url ending in '.html' is kept, e.g. 'About Me/404.html'
+/// is 'about-me/404.html'.
+fn get_sanitized_url(url: &str) -> String {
+    if let Some(path) = url.strip_suffix(".html") {
+        return format!("{}.html", Helper::sanitize_path(path));
+    }
+
+    return Helper::sanitize_path(url);
+}
@@ -0,0 +1,85 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use std::path::PathBuf;
+
+/// Where a site is read from and generated to, passed to every generator so that nothing
+/// depends on the working dir.
+#[derive(Clone, Debug)]
+pub struct SitePaths {
+    /// Dir with 'blog-posts', 'screenshots', 'topics', 'static' and 'static_root'.
+    pub source_dir: PathBuf,
+    /// Dir the site is generated in.
+    pub output_dir: PathBuf,
+    /// Site wide settings.
+    pub config_file: PathBuf,
+    /// Hashes of the last build.
+    pub cache_file: PathBuf,
+}
+
+impl SitePaths {
+    /// Paths of the site in `source_dir` with the defaults 'output', 'site.conf' and
+    /// '.flow-web-cache' inside it.
+    pub fn new(source_dir: PathBuf) -> Self {
+        Self {
+            output_dir: source_dir.join("output"),
+            config_file: source_dir.join("site.conf"),
+            cache_file: source_dir.join(".flow-web-cache"),
+            source_dir,
+        }
+    }
+
+    pub fn get_blog_posts_dir(&self) -> PathBuf {
+        return self.source_dir.join("blog-posts");
+    }
+
+    pub fn get_screenshots_dir(&self) -> PathBuf {
+        return self.source_dir.join("screenshots");
+    }
+
+    pub fn get_topics_dir(&self) -> PathBuf {
+        return self.source_dir.join("topics");
+    }
+
+    pub fn get_pages_dir(&self) -> PathBuf {
+        return self.source_dir.join("pages");
+    }
+
+    pub fn get_projects_dir(&self) -> PathBuf {
+        return self.source_dir.join("projects");
+    }
+
+    pub fn get_static_dir(&self) -> PathBuf {
+        return self.source_dir.join("static");
+    }
+
+    pub fn get_static_root_dir(&self) -> PathBuf {
+        return self.source_dir.join("static_root");
+    }
+
+    pub fn get_themes_dir(&self) -> PathBuf {
+        return self.source_dir.join("themes");
+    }
+
+    /// Dir a build is generated in before it replaces the output dir, next to the output
+    /// dir so both are on the same file system, e.g. '.output.staging'.
+    pub fn get_staging_dir(&self) -> PathBuf {
+        return self.get_output_sibling_dir("staging");
+    }
+
+    /// Dir the previous output is moved to while it is replaced, e.g. '.output.replaced'.
+    pub fn get_replaced_dir(&self) -> PathBuf {
+        return self.get_output_sibling_dir("replaced");
+    }
+
+    fn get_output_sibling_dir(&self, suffix: &str) -> PathBuf {
+        let name = match self.output_dir.file_name() {
+            Some(value) => value.to_string_lossy().into_owned(),
+            _ => String::from("output"),
+        };
+
+        return self
+            .output_dir
+            .with_file_name(format!(".{}.{}", name, suffix));
+    }
+}
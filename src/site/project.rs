@@ -0,0 +1,286 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use sailfish::Template;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::site::build_cache::BuildCache;
+use crate::site::error::SiteError;
+use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
+use crate::site::screenshot::Screenshot;
+use crate::site::template::Value;
+use crate::site::theme::{Theme, ThemeTemplate};
+
+/// One point of the summary of a featured project on the root index, e.g. 'Libre.'
+/// followed by a sentence about the license.
+#[derive(Clone, Debug)]
+pub struct ProjectFeature {
+    pub title: String,
+    /// Inline html of the markdown (or html) text, e.g. with links to the project.
+    pub html: String,
+}
+
+/// A project made from a manifest with markdown body in 'projects', e.g.
+/// 'projects/hfge.md'.
+#[derive(Template)]
+#[template(path = "project.stpl")]
+#[derive(Clone, Debug)]
+pub struct Project {
+    pub source_path: PathBuf,
+
+    pub name: String,
+    pub url: String,
+    pub description: String,
+    pub keywords: String,
+    pub repo: String,
+    pub license: String,
+    pub gallery: String,
+    pub featured: bool,
+    pub features: Vec<ProjectFeature>,
+    pub html: String,
+    pub screenshots: Vec<Screenshot>,
+    pub menu: Arc<Menu>,
+}
+
+impl Project {
+    pub fn new() -> Self {
+        Self {
+            source_path: PathBuf::new(),
+
+            name: String::new(),
+            url: String::new(),
+            description: String::new(),
+            keywords: String::new(),
+            repo: String::new(),
+            license: String::new(),
+            gallery: String::new(),
+            featured: false,
+            features: Vec::new(),
+            html: String::new(),
+            screenshots: Vec::new(),
+            menu: Arc::new(Menu::new()),
+        }
+    }
+
+    pub fn get_output_file(&self, paths: &SitePaths) -> PathBuf {
+        return paths.output_dir.join(&self.url).join("index.html");
+    }
+
+    /// Fields shown where this project is listed, for runtime templates.
+    pub fn get_summary_context(&self) -> Value {
+        return Value::map(vec![
+            ("name", Value::from(&self.name)),
+            ("url", Value::from(&self.url)),
+            ("description", Value::from(&self.description)),
+            ("license", Value::from(&self.license)),
+            (
+                "features",
+                Value::from(
+                    self.features
+                        .iter()
+                        .map(|feature| {
+                            Value::map(vec![
+                                ("title", Value::from(&feature.title)),
+                                ("html", Value::from(&feature.html)),
+                            ])
+                        })
+                        .collect::<Vec<Value>>(),
+                ),
+            ),
+        ]);
+    }
+
+    pub fn generate(
+        &self,
+        paths: &SitePaths,
+        cache: &BuildCache,
+        theme: &Theme,
+    ) -> Result<(), SiteError> {
+        // create output dir needed:
+        Helper::create_dir_all(&paths.output_dir.join(&self.url))?;
+
+        // write page to disk:
+        let output_file = self.get_output_file(paths);
+        let html = theme.render(self, &output_file)?;
+
+        return cache.write_file_sync(&output_file, html.as_bytes());
+    }
+}
+
+impl ThemeTemplate for Project {
+    fn get_template_name(&self) -> &str {
+        return "project.html";
+    }
+
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
+
+    fn get_context(&self) -> Value {
+        let mut context = self.get_summary_context();
+        context.insert("keywords", Value::from(&self.keywords));
+        context.insert("repo", Value::from(&self.repo));
+        context.insert("gallery", Value::from(&self.gallery));
+        context.insert(
+            "canonical_url",
+            Value::from(Helper::get_absolute_url(&self.url)),
+        );
+        context.insert("html", Value::from(&self.html));
+        context.insert(
+            "screenshots",
+            Value::from(
+                self.screenshots
+                    .iter()
+                    .map(|screenshot| screenshot.get_summary_context())
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+
+        return context;
+    }
+}
+
+pub async fn parse_project_file(
+    path: PathBuf,
+    projects_dir: &Path,
+    base_dir: &str,
+) -> Result<Project, SiteError> {
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| SiteError::io("read", &path, e))?;
+    let mut body_start_found = false;
+    let mut markdown = String::new();
+    let mut project = Project::new();
+    project.source_path = path.clone();
+
+    // the file path (without extension) relative to 'projects' is the url of the
+    // project, e.g. 'projects/hfge.md' is at '/projects/hfge/':
+    project.url = match path.strip_prefix(projects_dir) {
+        Ok(value) => format!(
+            "{}/{}",
+            base_dir,
+            Helper::sanitize_path(&value.with_extension("").to_string_lossy())
+        ),
+        _ => {
+            return Err(SiteError::parse(
+                &path,
+                None,
+                "Unable to get project url from path.",
+            ));
+        }
+    };
+
+    for (i, line) in contents.lines().enumerate() {
+        let error = |message: &str| SiteError::parse(&path, Some(i + 1), message);
+        if !body_start_found && !line.is_empty() {
+            if line == "---" {
+                body_start_found = true;
+                continue;
+            }
+
+            let v: Vec<&str> = line.splitn(2, ':').collect();
+            if v.len() != 2 {
+                return Err(error("Expected a line like 'key: value'."));
+            }
+
+            if let Some(key) = v.first() {
+                if *key == "name" {
+                    if let Some(value) = v.get(1) {
+                        project.name = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'name'."));
+                    }
+                } else if *key == "description" {
+                    if let Some(value) = v.get(1) {
+                        project.description = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'description'."));
+                    }
+                } else if *key == "keywords" {
+                    if let Some(value) = v.get(1) {
+                        project.keywords = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'keywords'."));
+                    }
+                } else if *key == "repo" {
+                    if let Some(value) = v.get(1) {
+                        project.repo = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'repo'."));
+                    }
+                } else if *key == "license" {
+                    if let Some(value) = v.get(1) {
+                        project.license = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'license'."));
+                    }
+                } else if *key == "gallery" {
+                    // url of the screenshots, as 'screenshots_url' in 'screenshots':
+                    if let Some(value) = v.get(1) {
+                        project.gallery = String::from(value.trim().trim_matches('/'));
+                    } else {
+                        return Err(error("Unable to parse field: 'gallery'."));
+                    }
+                } else if *key == "featured" {
+                    match v.get(1).map(|value| value.trim()) {
+                        Some("true") => project.featured = true,
+                        Some("false") => project.featured = false,
+                        _ => return Err(error("Unable to parse field: 'featured'.")),
+                    }
+                } else if *key == "feature" {
+                    // 'feature: <title>: <text>', may be given more than once:
+                    match v.get(1).and_then(|value| value.split_once(':')) {
+                        Some((title, text)) if !title.trim().is_empty() => {
+                            project.features.push(ProjectFeature {
+                                title: String::from(title.trim()),
+                                html: get_feature_html(text.trim()).map_err(|e| error(&e))?,
+                            });
+                        }
+                        _ => return Err(error("Unable to parse field: 'feature'.")),
+                    }
+                }
+            }
+        } else {
+            if !markdown.is_empty() {
+                markdown += "\n";
+            }
+            markdown += line;
+        }
+    }
+
+    if project.name.is_empty() {
+        return Err(SiteError::parse(&path, None, "Missing field: 'name'."));
+    }
+
+    // convert markdown to html:
+    project.html = markdown::to_html(&markdown);
+
+    return Ok(project);
+}
+
+/// Converts the markdown `text` of a feature line to html without the enclosing
+/// paragraph, so it can be shown inline. Projects are written by the site owner, so
+/// html is allowed as well.
+fn get_feature_html(text: &str) -> Result<String, String> {
+    let options = markdown::Options {
+        compile: markdown::CompileOptions {
+            allow_dangerous_html: true,
+            ..markdown::CompileOptions::default()
+        },
+        ..markdown::Options::default()
+    };
+    let html = markdown::to_html_with_options(text, &options).map_err(|e| e.to_string())?;
+
+    return Ok(
+        match html
+            .strip_prefix("<p>")
+            .and_then(|v| v.strip_suffix("</p>"))
+        {
+            Some(value) => String::from(value),
+            _ => html,
+        },
+    );
+}
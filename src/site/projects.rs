@@ -0,0 +1,127 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use sailfish::Template;
+use std::sync::Arc;
+
+use crate::site::build_cache::BuildCache;
+use crate::site::error::SiteError;
+use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
+use crate::site::project::{Project, parse_project_file};
+use crate::site::screenshots::ScreenshotsShared;
+use crate::site::sitemap::SitemapShared;
+use crate::site::template::Value;
+use crate::site::theme::{Theme, ThemeTemplate};
+
+/// Dir of the project pages and their index in the output dir.
+pub const BASE_DIR: &str = "projects";
+
+/// Number of screenshots of its gallery shown on a project page.
+const NUM_PROJECT_SCREENSHOTS: usize = 6;
+
+#[derive(Template)]
+#[template(path = "projects.stpl")]
+struct ProjectsTemplate {
+    base_dir: String,
+    projects: Vec<Project>,
+    menu: Arc<Menu>,
+}
+
+impl ThemeTemplate for ProjectsTemplate {
+    fn get_template_name(&self) -> &str {
+        return "projects.html";
+    }
+
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
+
+    fn get_context(&self) -> Value {
+        return Value::map(vec![
+            ("base_dir", Value::from(&self.base_dir)),
+            (
+                "canonical_url",
+                Value::from(Helper::get_absolute_url(&self.base_dir)),
+            ),
+            (
+                "projects",
+                Value::from(
+                    self.projects
+                        .iter()
+                        .map(|project| project.get_summary_context())
+                        .collect::<Vec<Value>>(),
+                ),
+            ),
+        ]);
+    }
+}
+
+/// Reads all projects in 'projects' of the site at `paths`, ordered by file name (which
+/// is also the order of their menu entries).
+pub async fn parse_projects(paths: &SitePaths) -> Result<Vec<Project>, SiteError> {
+    let projects_dir = paths.get_projects_dir();
+    let mut projects = Vec::new();
+
+    // projects are optional:
+    if !Helper::exists_dir(&projects_dir) {
+        return Ok(projects);
+    }
+
+    let mut files = Helper::get_files_recursive(&projects_dir)?;
+    files.sort();
+    for path in files {
+        if path.extension().is_none_or(|extension| extension != "md") {
+            continue;
+        }
+
+        projects.push(parse_project_file(path, &projects_dir, BASE_DIR).await?);
+    }
+
+    return Ok(projects);
+}
+
+/// Writes a page for every project, with some screenshots of its gallery, and the index
+/// listing them.
+pub async fn generate_projects(
+    projects: Vec<Project>,
+    screenshots: Arc<ScreenshotsShared>,
+    menu: Arc<Menu>,
+    paths: Arc<SitePaths>,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: Arc<Theme>,
+) -> Result<(), SiteError> {
+    if projects.is_empty() {
+        return Ok(());
+    }
+
+    let mut projects = projects;
+    for project in &mut projects {
+        if !project.gallery.is_empty() {
+            project.screenshots = screenshots
+                .get_screenshots(NUM_PROJECT_SCREENSHOTS, &project.gallery)
+                .map_err(|e| SiteError::parse(&project.source_path, None, &e.to_string()))?;
+        }
+
+        // rendered every build as the screenshots may have changed, unchanged pages
+        // aren't written:
+        project.generate(&paths, &cache, &theme)?;
+        sitemap.add_page(&project.url, None);
+    }
+
+    // index of all projects:
+    let ctx = ProjectsTemplate {
+        base_dir: String::from(BASE_DIR),
+        projects,
+        menu,
+    };
+    Helper::create_dir_all(&paths.output_dir.join(&ctx.base_dir))?;
+    let output_file = paths.output_dir.join(&ctx.base_dir).join("index.html");
+    let html = theme.render(&ctx, &output_file)?;
+    cache.write_file_sync(&output_file, html.as_bytes())?;
+    sitemap.add_page(&ctx.base_dir, None);
+
+    Ok(())
+}
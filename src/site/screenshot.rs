@@ -2,8 +2,16 @@
 // AGPL-3.0 License (see LICENSE)
 
 use sailfish::Template;
+use std::path::PathBuf;
+use std::sync::Arc;
 
+use crate::site::build_cache::BuildCache;
+use crate::site::error::SiteError;
 use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
+use crate::site::template::Value;
+use crate::site::theme::{Theme, ThemeTemplate};
 
 #[derive(Template)]
 #[template(path = "screenshot.stpl")]
@@ -16,10 +24,11 @@ pub struct Screenshot {
     pub image_big: String,
     pub url: String,
     pub screenshots: Vec<Screenshot>,
+    pub menu: Arc<Menu>,
 }
 
 impl Screenshot {
-    pub fn new() -> Self {
+    pub fn new(menu: Arc<Menu>) -> Self {
         Self {
             screenshots_title: String::new(),
             screenshots_url: String::new(),
@@ -28,22 +37,72 @@ impl Screenshot {
             image_big: String::new(),
             url: String::new(),
             screenshots: Vec::new(),
+            menu,
         }
     }
 
-    pub fn generate(&self) {
+    pub fn get_output_file(&self, paths: &SitePaths) -> PathBuf {
+        return paths.output_dir.join(&self.url).join("index.html");
+    }
+
+    /// Fields shown where this screenshot is listed, for runtime templates.
+    pub fn get_summary_context(&self) -> Value {
+        return Value::map(vec![
+            ("title", Value::from(&self.title)),
+            ("url", Value::from(&self.url)),
+            ("image_min", Value::from(&self.image_min)),
+            ("image_big", Value::from(&self.image_big)),
+        ]);
+    }
+
+    pub fn generate(
+        &self,
+        paths: &SitePaths,
+        cache: &BuildCache,
+        theme: &Theme,
+    ) -> Result<(), SiteError> {
         // create output dir needed:
-        self.create_output_dir();
+        self.create_output_dir(paths)?;
 
         // write page to disk:
-        Helper::write_file_sync(
-            &Helper::get_output_dir().join(&self.url).join("index.html"),
-            &self.render().unwrap().as_bytes(),
-        )
-        .unwrap();
+        let output_file = self.get_output_file(paths);
+        let html = theme.render(self, &output_file)?;
+
+        return cache.write_file_sync(&output_file, html.as_bytes());
+    }
+
+    fn create_output_dir(&self, paths: &SitePaths) -> Result<(), SiteError> {
+        return Helper::create_dir_all(&paths.output_dir.join(&self.url));
     }
+}
+
+impl ThemeTemplate for Screenshot {
+    fn get_template_name(&self) -> &str {
+        return "screenshot.html";
+    }
+
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
+
+    fn get_context(&self) -> Value {
+        let mut context = self.get_summary_context();
+        context.insert(
+            "canonical_url",
+            Value::from(Helper::get_absolute_url(&self.url)),
+        );
+        context.insert("screenshots_title", Value::from(&self.screenshots_title));
+        context.insert("screenshots_url", Value::from(&self.screenshots_url));
+        context.insert(
+            "screenshots",
+            Value::from(
+                self.screenshots
+                    .iter()
+                    .map(|screenshot| screenshot.get_summary_context())
+                    .collect::<Vec<Value>>(),
+            ),
+        );
 
-    fn create_output_dir(&self) {
-        Helper::create_dir_all(&Helper::get_output_dir().join(&self.url));
+        return context;
     }
 }
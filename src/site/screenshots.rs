@@ -2,212 +2,169 @@
 // AGPL-3.0 License (see LICENSE)
 
 use sailfish::Template;
-use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+use crate::site::build_cache::BuildCache;
+use crate::site::error::SiteError;
+use crate::site::gallery::{Gallery, parse_gallery_file};
 use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
 use crate::site::screenshot::Screenshot;
+use crate::site::sitemap::SitemapShared;
+use crate::site::template::Value;
+use crate::site::theme::{Theme, ThemeTemplate};
 
-#[derive(Clone, Debug)]
-pub struct ScreenshotsInfo {
-    pub title: String,
-    pub url: String,
-}
-
-impl ScreenshotsInfo {
-    pub fn new() -> Self {
-        Self {
-            title: String::new(),
-            url: String::new(),
-        }
-    }
-}
+/// Dir of the overview of all galleries in the output dir.
+pub const BASE_DIR: &str = "screenshots";
 
 pub struct ScreenshotsShared {
     state: Mutex<ScreenshotsState>,
 }
 
 impl ScreenshotsShared {
-    pub fn new() -> Self {
+    pub fn new(galleries: Vec<Gallery>, menu: Arc<Menu>) -> Self {
         Self {
-            state: Mutex::new(ScreenshotsState::new()),
+            state: Mutex::new(ScreenshotsState::new(galleries, menu)),
         }
     }
 
-    pub fn get_screenshots(&self, num_screenshots: usize, key: &str) -> Vec<Screenshot> {
+    /// Returns the first `num_screenshots` screenshots of the gallery at url `gallery`.
+    pub fn get_screenshots(
+        &self,
+        num_screenshots: usize,
+        gallery: &str,
+    ) -> Result<Vec<Screenshot>, SiteError> {
         let lock = self.state.lock().unwrap();
-        let mut screenshots = Vec::new();
-
-        if let Some(value) = lock.screenshots.get(key) {
-            for i in 0..num_screenshots {
-                if let Some(screenshot) = value.get(i) {
-                    screenshots.push(screenshot.clone());
-                }
-            }
-        } else {
-            panic!("Did not find any screenshots for key: {}", key);
+
+        if let Some(value) = lock.galleries.iter().find(|value| value.url == gallery) {
+            return Ok(value
+                .screenshots
+                .iter()
+                .take(num_screenshots)
+                .cloned()
+                .collect());
         }
 
-        return screenshots;
+        return Err(SiteError::content(&format!(
+            "Did not find any screenshots for gallery: '{}'.",
+            gallery
+        )));
     }
 }
 
+/// All galleries, rendered as their overview at '/screenshots/'.
 #[derive(Template)]
-#[template(path = "screenshots.stpl")]
+#[template(path = "galleries.stpl")]
 #[derive(Clone, Debug)]
 struct ScreenshotsState {
-    screenshots: HashMap<String, Vec<Screenshot>>,
-    screenshot_urls: HashMap<String, String>,
-    title: String,
-    url: String,
+    base_dir: String,
+    galleries: Vec<Gallery>,
+    menu: Arc<Menu>,
 }
 
 impl ScreenshotsState {
-    pub fn new() -> Self {
-        Self {
-            screenshots: HashMap::new(),
-            screenshot_urls: HashMap::new(),
-            title: String::new(),
-            url: String::new(),
+    pub fn new(galleries: Vec<Gallery>, menu: Arc<Menu>) -> Self {
+        let mut galleries = galleries;
+        for gallery in &mut galleries {
+            gallery.set_menu(menu.clone());
         }
-    }
-}
 
-pub async fn generate_screenshots(shared: Arc<ScreenshotsShared>) {
-    parse_files(shared.clone(), "screenshots").await;
-}
-
-async fn parse_files(shared: Arc<ScreenshotsShared>, base_dir: &str) {
-    let mut reader = tokio::fs::read_dir(base_dir).await.unwrap();
-    loop {
-        if let Some(f) = reader.next_entry().await.unwrap() {
-            let contents = tokio::fs::read_to_string(f.path()).await.unwrap();
-
-            parse_file(shared.clone(), contents);
-            update_screenshots(shared.clone());
-            generate(shared.clone());
-        } else {
-            break;
+        Self {
+            base_dir: String::from(BASE_DIR),
+            galleries,
+            menu,
         }
     }
 }
 
-fn parse_file(shared: Arc<ScreenshotsShared>, contents: String) {
-    let mut screenshots_info = ScreenshotsInfo::new();
-    let mut screenshot = Screenshot::new();
-
-    for line in contents.lines() {
-        if line.len() == 0 {
-            continue;
-        }
-
-        let v: Vec<&str> = line.splitn(2, ':').collect();
-        assert_eq!(v.len(), 2);
-
-        if let Some(key) = v.get(0) {
-            if *key == "screenshots_title" {
-                if let Some(value) = v.get(1) {
-                    screenshots_info.title = String::from(value.trim());
-                } else {
-                    panic!("Unable to parse field: 'screenshots_title'.");
-                }
-            } else if *key == "screenshots_url" {
-                if let Some(value) = v.get(1) {
-                    screenshots_info.url = String::from(value.trim());
-                } else {
-                    panic!("Unable to parse field: 'screenshots_url'.");
-                }
-            } else if *key == "title" {
-                if let Some(value) = v.get(1) {
-                    screenshot.title = String::from(value.trim());
-                } else {
-                    panic!("Unable to parse field: 'title'.");
-                }
-            } else if *key == "image_min" {
-                if let Some(value) = v.get(1) {
-                    screenshot.image_min = String::from(value.trim());
-                } else {
-                    panic!("Unable to parse field: 'image_min'.");
-                }
-            } else if *key == "image_big" {
-                if let Some(value) = v.get(1) {
-                    screenshot.image_big = String::from(value.trim());
-                } else {
-                    panic!("Unable to parse field: 'image_big'.");
-                }
-            } else if *key == "url" {
-                if let Some(value) = v.get(1) {
-                    screenshot.url = String::from(value.trim());
-                } else {
-                    panic!("Unable to parse field: 'url'.");
-                }
-            }
-        }
-
-        // all info needed for one screenshot:
-        if screenshot.title.len() > 0
-            && screenshot.image_min.len() > 0
-            && screenshot.image_big.len() > 0
-            && screenshot.url.len() > 0
-            && screenshots_info.title.len() > 0
-            && screenshots_info.url.len() > 0
-        {
-            screenshot.screenshots_title = screenshots_info.title.clone();
-            screenshot.screenshots_url = screenshots_info.url.clone();
-
-            {
-                let mut lock = shared.state.lock().unwrap();
-
-                if let Some(value) = lock.screenshots.get_mut(&screenshots_info.title) {
-                    value.push(screenshot);
-                } else {
-                    lock.screenshot_urls
-                        .insert(screenshots_info.title.clone(), screenshots_info.url.clone());
-                    lock.screenshots
-                        .insert(screenshots_info.title.clone(), vec![screenshot]);
-                }
-            }
-
-            // reset for new screenshot:
-            screenshot = Screenshot::new();
-        }
+impl ThemeTemplate for ScreenshotsState {
+    fn get_template_name(&self) -> &str {
+        return "galleries.html";
     }
 
-    let mut lock = shared.state.lock().unwrap();
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
 
-    lock.title = screenshots_info.title.clone();
-    lock.url = screenshots_info.url.clone();
+    fn get_context(&self) -> Value {
+        return Value::map(vec![
+            ("base_dir", Value::from(&self.base_dir)),
+            (
+                "canonical_url",
+                Value::from(Helper::get_absolute_url(&self.base_dir)),
+            ),
+            (
+                "galleries",
+                Value::from(
+                    self.galleries
+                        .iter()
+                        .map(|gallery| gallery.get_summary_context())
+                        .collect::<Vec<Value>>(),
+                ),
+            ),
+        ]);
+    }
 }
 
-fn update_screenshots(shared: Arc<ScreenshotsShared>) {
-    // TODO: this could be done more efficiently:
-    // (need screenshots for each screenshot as they are linked below)
-    let mut lock = shared.state.lock().unwrap();
-    for (_key, value) in &mut lock.screenshots {
-        let v = value.clone();
-        for s in value {
-            s.screenshots = v.clone();
+/// Reads every file in 'screenshots' of the site at `paths` as one gallery, ordered by
+/// file name (which is also the order of their menu entries).
+pub async fn parse_galleries(paths: &SitePaths) -> Result<Vec<Gallery>, SiteError> {
+    let mut galleries: Vec<Gallery> = Vec::new();
+
+    let mut files = Helper::get_files_recursive(&paths.get_screenshots_dir())?;
+    files.sort();
+    for path in files {
+        let gallery = parse_gallery_file(path).await?;
+        if gallery.url == BASE_DIR {
+            return Err(SiteError::parse(
+                &gallery.source_path,
+                None,
+                &format!(
+                    "Gallery url: '{}' is used by the overview of all galleries.",
+                    gallery.url
+                ),
+            ));
+        }
+        if let Some(other) = galleries.iter().find(|other| other.url == gallery.url) {
+            return Err(SiteError::parse(
+                &gallery.source_path,
+                None,
+                &format!(
+                    "Gallery url: '{}' is already used by '{}'.",
+                    gallery.url,
+                    other.source_path.display()
+                ),
+            ));
         }
+        galleries.push(gallery);
     }
+
+    return Ok(galleries);
 }
 
-fn generate(shared: Arc<ScreenshotsShared>) {
+/// Writes the pages of every gallery and the overview listing them.
+pub async fn generate_screenshots(
+    shared: Arc<ScreenshotsShared>,
+    paths: Arc<SitePaths>,
+    sitemap: Arc<SitemapShared>,
+    cache: Arc<BuildCache>,
+    theme: Arc<Theme>,
+) -> Result<(), SiteError> {
     let lock = shared.state.lock().unwrap();
 
-    for (_key, screenshots) in &lock.screenshots {
-        // create output dir needed:
-        Helper::create_dir_all(&Helper::get_output_dir().join(&lock.url));
+    // only render pages of a gallery again if its file changed since the last build:
+    for gallery in &lock.galleries {
+        gallery.generate(&paths, &sitemap, &cache, &theme)?;
+    }
 
-        // write page to disk:
-        Helper::write_file_sync(
-            &Helper::get_output_dir().join(&lock.url).join("index.html"),
-            &lock.render().unwrap().as_bytes(),
-        )
-        .unwrap();
+    // overview of all galleries, rendered every build as any gallery may have changed,
+    // unchanged pages aren't written:
+    Helper::create_dir_all(&paths.output_dir.join(&lock.base_dir))?;
+    let output_file = paths.output_dir.join(&lock.base_dir).join("index.html");
+    let html = theme.render(&*lock, &output_file)?;
+    cache.write_file_sync(&output_file, html.as_bytes())?;
+    sitemap.add_page(&lock.base_dir, None);
 
-        // generate all individual screenshot pages:
-        for screenshot in screenshots {
-            screenshot.generate();
-        }
-    }
+    Ok(())
 }
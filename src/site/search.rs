@@ -0,0 +1,128 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use regex::Regex;
+use sailfish::Template;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::site::blog_post::BlogPost;
+use crate::site::build_cache::BuildCache;
+use crate::site::error::SiteError;
+use crate::site::feed::get_json_string;
+use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
+use crate::site::template::Value;
+use crate::site::theme::{Theme, ThemeTemplate};
+
+/// Words shorter than this are left out of the search index.
+const MIN_WORD_LENGTH: usize = 3;
+
+#[derive(Template)]
+#[template(path = "search.stpl")]
+struct SearchTemplate {
+    menu: Arc<Menu>,
+}
+
+impl ThemeTemplate for SearchTemplate {
+    fn get_template_name(&self) -> &str {
+        return "search.html";
+    }
+
+    fn get_menu(&self) -> &Menu {
+        return &self.menu;
+    }
+
+    fn get_context(&self) -> Value {
+        return Value::map(vec![(
+            "canonical_url",
+            Value::from(Helper::get_absolute_url("search")),
+        )]);
+    }
+}
+
+/// Writes the search page and its index to 'search/', the index is queried in the
+/// browser by 'static/scripts/search.js' so no server side support is needed. The page
+/// is 'noindex', so it is left out of the sitemap.
+pub fn generate_search(
+    blog_posts: &[BlogPost],
+    menu: Arc<Menu>,
+    paths: &SitePaths,
+    cache: &BuildCache,
+    theme: &Theme,
+) -> Result<(), SiteError> {
+    Helper::create_dir_all(&paths.output_dir.join("search"))?;
+
+    cache.write_file_sync(
+        &paths.output_dir.join("search/index.json"),
+        get_search_index(blog_posts).as_bytes(),
+    )?;
+
+    let ctx = SearchTemplate { menu };
+    let output_file = paths.output_dir.join("search/index.html");
+    let html = theme.render(&ctx, &output_file)?;
+    cache.write_file_sync(&output_file, html.as_bytes())?;
+
+    Ok(())
+}
+
+/// Builds the JSON search index, one entry per blog post (latest first) with the fields
+/// shown in the results and the unique words of the post body.
+fn get_search_index(blog_posts: &[BlogPost]) -> String {
+    let mut index = String::from("[");
+
+    for (i, post) in blog_posts.iter().enumerate() {
+        if i > 0 {
+            index += ",";
+        }
+
+        let topics: Vec<String> = post
+            .get_topics()
+            .iter()
+            .map(|topic| get_json_string(topic))
+            .collect();
+
+        index += "\n{";
+        index += &format!("\"title\":{},", get_json_string(&post.title));
+        index += &format!(
+            "\"url\":{},",
+            get_json_string(&format!("/{}/{}/", post.base_dir, post.url))
+        );
+        index += &format!(
+            "\"date\":{},",
+            get_json_string(&Helper::format_date(&post.published, &post.date_format))
+        );
+        index += &format!(
+            "\"datetime\":{},",
+            get_json_string(&Helper::get_date_for_html(&post.published))
+        );
+        index += &format!("\"topics\":[{}],", topics.join(","));
+        index += &format!("\"snippet\":{},", get_json_string(&post.snippet));
+        index += &format!("\"words\":{}", get_json_string(&get_words(&post.html)));
+        index += "}";
+    }
+    index += "\n]\n";
+
+    return index;
+}
+
+/// Returns the unique lowercase words of `html` separated by spaces, in order of first
+/// appearance.
+fn get_words(html: &str) -> String {
+    let re_tags = Regex::new(r"<[^>]*>").unwrap();
+    let re_entities = Regex::new(r"&[#0-9a-zA-Z]+;").unwrap();
+    let text = re_tags.replace_all(html, " ");
+    let text = re_entities.replace_all(&text, " ");
+
+    let mut seen = HashSet::new();
+    let mut words = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let word = word.to_lowercase();
+        if word.chars().count() >= MIN_WORD_LENGTH && seen.insert(word.clone()) {
+            words.push(word);
+        }
+    }
+
+    return words.join(" ");
+}
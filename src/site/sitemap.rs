@@ -0,0 +1,125 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use std::sync::{Arc, Mutex};
+
+use crate::site::build_cache::BuildCache;
+use crate::site::config::SITE_URL;
+use crate::site::error::SiteError;
+use crate::site::helper::Helper;
+use crate::site::paths::SitePaths;
+use crate::site::xml_writer::XmlWriter;
+
+/// Maximum number of urls in one sitemap file as allowed by the sitemap protocol, larger
+/// sites get a sitemap index pointing at several sitemap files.
+const MAX_URLS_PER_SITEMAP: usize = 50000;
+
+#[derive(Clone, Debug)]
+struct SitemapEntry {
+    path: String,
+    lastmod: Option<DateTime<FixedOffset>>,
+}
+
+/// Collects every page written during the build, generators add their pages as they
+/// write them.
+pub struct SitemapShared {
+    entries: Mutex<Vec<SitemapEntry>>,
+}
+
+impl SitemapShared {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Adds the page at `path` (relative to the output dir, "" for the root index) with
+    /// the date its content was last modified (if known).
+    pub fn add_page(&self, path: &str, lastmod: Option<DateTime<FixedOffset>>) {
+        self.entries.lock().unwrap().push(SitemapEntry {
+            path: String::from(path),
+            lastmod,
+        });
+    }
+}
+
+pub async fn generate_sitemap(
+    shared: Arc<SitemapShared>,
+    paths: Arc<SitePaths>,
+    cache: Arc<BuildCache>,
+) -> Result<(), SiteError> {
+    let mut entries = shared.entries.lock().unwrap().clone();
+
+    // pages are added from concurrent tasks, sort them so output is the same every build:
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries.dedup_by(|a, b| a.path == b.path);
+
+    if entries.len() <= MAX_URLS_PER_SITEMAP {
+        write_sitemap(&entries, "sitemap.xml", &paths, &cache)?;
+    } else {
+        let mut xml = XmlWriter::new();
+        xml.start(
+            "sitemapindex",
+            &[("xmlns", "http://www.sitemaps.org/schemas/sitemap/0.9")],
+        );
+
+        for (i, chunk) in entries.chunks(MAX_URLS_PER_SITEMAP).enumerate() {
+            let file_name = format!("sitemap-{}.xml", i + 1);
+            write_sitemap(chunk, &file_name, &paths, &cache)?;
+
+            xml.start("sitemap", &[]);
+            xml.element("loc", &[], &format!("{}/{}", SITE_URL, file_name));
+            if let Some(lastmod) = chunk.iter().filter_map(|entry| entry.lastmod).max() {
+                xml.element("lastmod", &[], &get_date_for_sitemap(&lastmod));
+            }
+            xml.end();
+        }
+
+        xml.end();
+        cache.write_file_sync(
+            &paths.output_dir.join("sitemap.xml"),
+            xml.finish()?.as_bytes(),
+        )?;
+    }
+
+    // let crawlers know where to find the sitemap:
+    return cache.write_file_sync(
+        &paths.output_dir.join("robots.txt"),
+        format!(
+            "User-agent: *\nAllow: /\n\nSitemap: {}/sitemap.xml\n",
+            SITE_URL
+        )
+        .as_bytes(),
+    );
+}
+
+fn write_sitemap(
+    entries: &[SitemapEntry],
+    file_name: &str,
+    paths: &SitePaths,
+    cache: &BuildCache,
+) -> Result<(), SiteError> {
+    let mut xml = XmlWriter::new();
+    xml.start(
+        "urlset",
+        &[("xmlns", "http://www.sitemaps.org/schemas/sitemap/0.9")],
+    );
+
+    for entry in entries {
+        xml.start("url", &[]);
+        xml.element("loc", &[], &Helper::get_absolute_url(&entry.path));
+        if let Some(lastmod) = &entry.lastmod {
+            xml.element("lastmod", &[], &get_date_for_sitemap(lastmod));
+        }
+        xml.end();
+    }
+
+    xml.end();
+    return cache.write_file_sync(&paths.output_dir.join(file_name), xml.finish()?.as_bytes());
+}
+
+/// W3C datetime as used by sitemaps, e.g. "2022-01-13T16:24:00+01:00".
+fn get_date_for_sitemap(date: &DateTime<FixedOffset>) -> String {
+    return date.to_rfc3339_opts(SecondsFormat::Secs, false);
+}
@@ -0,0 +1,133 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use chrono::Month;
+use std::collections::HashMap;
+
+use crate::site::error::SiteError;
+use crate::site::helper::Helper;
+use crate::site::topic::TopicInfo;
+use crate::site::topic::get_topic_parent;
+use crate::site::topic::get_topic_with_parents;
+
+/// A grouping of blog posts such as topics or years. Every term of a taxonomy gets its
+/// own paginated overview at '<blog>/<name>/<term>/' and the taxonomy itself gets a
+/// landing page at '<blog>/<name>/' listing all terms.
+#[derive(Clone, Debug)]
+pub struct Taxonomy {
+    pub name: String,
+    pub title: String,
+    pub weighted: bool,
+    pub has_feed: bool,
+    pub terms: Vec<String>,
+    pub terms_sanitized: Vec<String>,
+    pub blog_indices: HashMap<String, Vec<usize>>,
+    pub infos: HashMap<String, TopicInfo>,
+}
+
+impl Taxonomy {
+    pub fn new(name: &str, title: &str, weighted: bool) -> Self {
+        Self {
+            name: String::from(name),
+            title: String::from(title),
+            weighted,
+            has_feed: false,
+            terms: Vec::new(),
+            terms_sanitized: Vec::new(),
+            blog_indices: HashMap::new(),
+            infos: HashMap::new(),
+        }
+    }
+
+    /// Creates a taxonomy configured in 'site.conf', title is the capitalized name.
+    pub fn from_config(name: &str) -> Self {
+        let mut chars = name.chars();
+        let title = match chars.next() {
+            Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+            _ => String::new(),
+        };
+
+        return Taxonomy::new(name, &title, true);
+    }
+
+    /// Adds blog post with index `blog_index` to each of `terms` (and their parent terms).
+    pub fn add_blog_post(&mut self, blog_index: usize, terms: &[String]) {
+        for term in terms {
+            for term_or_parent in get_topic_with_parents(term) {
+                if let Some(indices) = self.blog_indices.get_mut(&term_or_parent) {
+                    // post may already be added through another sub term:
+                    if indices.last() != Some(&blog_index) {
+                        indices.push(blog_index);
+                    }
+                } else {
+                    self.blog_indices.insert(term_or_parent, vec![blog_index]);
+                }
+            }
+        }
+    }
+
+    /// Collects all terms added so far, sorted by name (sub terms end up right after
+    /// their parent).
+    pub fn sort_terms(&mut self) -> Result<(), SiteError> {
+        self.terms.clear();
+        for key in self.blog_indices.keys() {
+            // a sub term named 'page' would collide with the pagination dirs:
+            if Helper::sanitize_path(key).ends_with("/page") {
+                return Err(SiteError::content(&format!(
+                    "Sub {}: '{}' can not be named 'page'.",
+                    self.name, key
+                )));
+            }
+            self.terms.push(String::from(key));
+        }
+
+        self.terms.sort_by_key(|a| a.to_lowercase());
+        self.terms_sanitized = self
+            .terms
+            .iter()
+            .map(|term| Helper::sanitize_path(term))
+            .collect();
+
+        Ok(())
+    }
+
+    pub fn get_num_blog_posts(&self, term: &str) -> usize {
+        return match self.blog_indices.get(term) {
+            Some(indices) => indices.len(),
+            _ => 0,
+        };
+    }
+
+    /// Readable name of `term`, e.g. "April 2019" for the month "2019/04" of the years,
+    /// other terms are returned as they are.
+    pub fn get_term_title(&self, term: &str) -> String {
+        if self.name == "year"
+            && let Some((year, month)) = term.split_once('/')
+            && let Some(month) = month
+                .parse::<u8>()
+                .ok()
+                .and_then(|m| Month::try_from(m).ok())
+        {
+            return format!("{} {}", month.name(), year);
+        }
+
+        return String::from(term);
+    }
+
+    pub fn get_root_terms(&self) -> Vec<(&String, &String)> {
+        return self
+            .terms
+            .iter()
+            .zip(&self.terms_sanitized)
+            .filter(|(term, _term_sanitized)| get_topic_parent(term).is_none())
+            .collect();
+    }
+
+    pub fn get_sub_terms(&self, term: &str) -> Vec<&String> {
+        return self
+            .terms
+            .iter()
+            .filter(|sub_term| get_topic_parent(sub_term) == Some(term))
+            .collect();
+    }
+}
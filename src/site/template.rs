@@ -0,0 +1,1084 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::site::error::SiteError;
+
+/// Filters that can be applied to `{{ value | filter }}` outputs.
+const FILTERS: [&str; 3] = ["safe", "lower", "upper"];
+
+/// Includes and extends nested deeper than this are most likely a cycle.
+const MAX_DEPTH: usize = 32;
+
+/// Data a runtime template is rendered with, built from the fields of a page.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    String(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Builds a map from `entries`, e.g. `Value::map(vec![("title", Value::from("Blog"))])`.
+    pub fn map(entries: Vec<(&str, Value)>) -> Self {
+        return Value::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (String::from(key), value))
+                .collect(),
+        );
+    }
+
+    /// Sets `key` of a map to `value`, does nothing for other values.
+    pub fn insert(&mut self, key: &str, value: Value) {
+        if let Value::Map(map) = self {
+            map.insert(String::from(key), value);
+        }
+    }
+
+    /// Empty strings and lists, `false`, `0` and missing values are false, everything else
+    /// is true.
+    fn is_true(&self) -> bool {
+        return match self {
+            Value::Null => false,
+            Value::Bool(value) => *value,
+            Value::Int(value) => *value != 0,
+            Value::String(value) => !value.is_empty(),
+            Value::List(value) => !value.is_empty(),
+            Value::Map(value) => !value.is_empty(),
+        };
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        return Value::String(String::from(value));
+    }
+}
+
+impl From<&String> for Value {
+    fn from(value: &String) -> Self {
+        return Value::String(value.clone());
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        return Value::String(value);
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        return Value::Bool(value);
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        return Value::Int(value as i64);
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        return Value::List(value);
+    }
+}
+
+/// Contents of a `{% %}` tag (e.g. 'extends "base.html"') and the line it's on.
+type Tag = (String, usize);
+
+#[derive(Debug)]
+enum Expr {
+    Path(Vec<String>),
+    String(String),
+    Int(i64),
+    Not(Box<Expr>),
+    Equal(Box<Expr>, Box<Expr>),
+    NotEqual(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Output {
+        expr: Expr,
+        filters: Vec<String>,
+        line: usize,
+    },
+    If {
+        branches: Vec<(Expr, Vec<Node>)>,
+        otherwise: Vec<Node>,
+        line: usize,
+    },
+    For {
+        name: String,
+        expr: Expr,
+        body: Vec<Node>,
+        line: usize,
+    },
+    Include {
+        name: String,
+        line: usize,
+    },
+    Block {
+        name: String,
+    },
+}
+
+/// A parsed runtime template.
+///
+/// # Syntax
+///
+/// ```
+/// {{ post.title }}                  escaped output, '| safe' outputs html as is
+/// {% if not post.cover %}...{% elif a == "b" %}...{% else %}...{% endif %}
+/// {% for post in posts %}{{ loop.index }}{% endfor %}
+/// {% include "partials/header.html" %}
+/// {% extends "base.html" %} {% block content %}...{% endblock %}
+/// {# comment #}
+/// ```
+#[derive(Debug)]
+pub struct Template {
+    path: PathBuf,
+    parent: Option<Tag>,
+    nodes: Vec<Node>,
+    blocks: HashMap<String, Vec<Node>>,
+}
+
+enum Segment {
+    Text(String),
+    Output(String, usize),
+    Tag(String, usize),
+}
+
+struct Parser<'a> {
+    path: &'a Path,
+    segments: Vec<Segment>,
+    pos: usize,
+    parent: Option<Tag>,
+    blocks: HashMap<String, Vec<Node>>,
+}
+
+impl Template {
+    /// Parses `source` read from `path`, the path is only used for error messages.
+    pub fn parse(path: &Path, source: &str) -> Result<Self, SiteError> {
+        let mut parser = Parser {
+            path,
+            segments: split_segments(path, source)?,
+            pos: 0,
+            parent: None,
+            blocks: HashMap::new(),
+        };
+
+        let (nodes, end) = parser.parse_nodes(&[])?;
+        if let Some((tag, line)) = end {
+            return Err(SiteError::parse(
+                path,
+                Some(line),
+                &format!("Unexpected '{{% {} %}}'.", tag),
+            ));
+        }
+
+        return Ok(Self {
+            path: path.to_path_buf(),
+            parent: parser.parent,
+            nodes,
+            blocks: parser.blocks,
+        });
+    }
+}
+
+/// Splits `source` into text, `{{ }}` outputs and `{% %}` tags, `{# #}` comments are
+/// dropped.
+fn split_segments(path: &Path, source: &str) -> Result<Vec<Segment>, SiteError> {
+    let mut segments = Vec::new();
+    let mut rest = source;
+    let mut line = 1;
+
+    while let Some(start) = rest.find('{') {
+        let (open, close) = match rest[start..].chars().nth(1) {
+            Some('{') => ("{{", "}}"),
+            Some('%') => ("{%", "%}"),
+            Some('#') => ("{#", "#}"),
+            _ => {
+                // a '{' on its own, e.g. in inline css or scripts:
+                segments.push(Segment::Text(String::from(&rest[..start + 1])));
+                line += rest[..start + 1].matches('\n').count();
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+
+        if start > 0 {
+            segments.push(Segment::Text(String::from(&rest[..start])));
+            line += rest[..start].matches('\n').count();
+        }
+
+        let inner_start = start + open.len();
+        let inner_end = match rest[inner_start..].find(close) {
+            Some(value) => inner_start + value,
+            _ => {
+                return Err(SiteError::parse(
+                    path,
+                    Some(line),
+                    &format!("Missing '{}' for '{}'.", close, open),
+                ));
+            }
+        };
+        let inner = rest[inner_start..inner_end].trim();
+        match open {
+            "{{" => segments.push(Segment::Output(String::from(inner), line)),
+            "{%" => segments.push(Segment::Tag(String::from(inner), line)),
+            _ => {}
+        }
+
+        line += rest[start..inner_end].matches('\n').count();
+        rest = &rest[inner_end + close.len()..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(Segment::Text(String::from(rest)));
+    }
+
+    return Ok(segments);
+}
+
+impl Parser<'_> {
+    /// Parses nodes until one of the tags in `end_tags` (e.g. 'endif'), returns the nodes
+    /// and the tag it stopped at with its arguments.
+    fn parse_nodes(&mut self, end_tags: &[&str]) -> Result<(Vec<Node>, Option<Tag>), SiteError> {
+        let mut nodes = Vec::new();
+
+        while self.pos < self.segments.len() {
+            let segment = &self.segments[self.pos];
+            self.pos += 1;
+
+            match segment {
+                Segment::Text(text) => nodes.push(Node::Text(text.clone())),
+                Segment::Output(inner, line) => {
+                    let line = *line;
+                    let mut tokens = tokenize(inner).map_err(|e| self.error(line, &e))?;
+
+                    // filters are applied last to first, split them off the expression:
+                    let mut filters = Vec::new();
+                    while let Some(i) = tokens.iter().rposition(|t| *t == Token::Pipe) {
+                        let filter = match &tokens[i + 1..] {
+                            [Token::Word(name)] if FILTERS.contains(&name.as_str()) => name.clone(),
+                            _ => return Err(self.error(line, "Unknown filter.")),
+                        };
+                        filters.insert(0, filter);
+                        tokens.truncate(i);
+                    }
+
+                    let expr = parse_expression(&tokens).map_err(|e| self.error(line, &e))?;
+                    nodes.push(Node::Output {
+                        expr,
+                        filters,
+                        line,
+                    });
+                }
+                Segment::Tag(inner, line) => {
+                    let line = *line;
+                    let inner = inner.clone();
+                    let (tag, args) = match inner.split_once(char::is_whitespace) {
+                        Some((tag, args)) => (tag, args.trim()),
+                        _ => (inner.as_str(), ""),
+                    };
+
+                    if end_tags.contains(&tag) {
+                        // only 'elif' takes a condition, e.g. '{% else x %}' is a typo:
+                        if tag != "elif" && !args.is_empty() {
+                            return Err(
+                                self.error(line, &format!("Unexpected '{{% {} %}}'.", inner))
+                            );
+                        }
+                        return Ok((nodes, Some((inner.clone(), line))));
+                    }
+
+                    match tag {
+                        "if" => nodes.push(self.parse_if(args, line)?),
+                        "for" => nodes.push(self.parse_for(args, line)?),
+                        "include" => nodes.push(Node::Include {
+                            name: self.parse_name(args, line)?,
+                            line,
+                        }),
+                        "extends" => {
+                            if self.parent.is_some() {
+                                return Err(self.error(line, "Only one 'extends' is allowed."));
+                            }
+                            self.parent = Some((self.parse_name(args, line)?, line));
+                        }
+                        "block" => nodes.push(self.parse_block(args, line)?),
+                        _ => {
+                            return Err(
+                                self.error(line, &format!("Unexpected '{{% {} %}}'.", inner))
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        // the end of the template is only expected at the top level:
+        if !end_tags.is_empty() {
+            return Err(self.error(
+                self.get_last_line(),
+                &format!("Missing '{{% {} %}}'.", end_tags[end_tags.len() - 1]),
+            ));
+        }
+
+        return Ok((nodes, None));
+    }
+
+    fn parse_if(&mut self, args: &str, line: usize) -> Result<Node, SiteError> {
+        let mut branches = Vec::new();
+        let mut otherwise = Vec::new();
+        let mut condition = self.parse_condition(args, line)?;
+
+        loop {
+            let (body, end) = self.parse_nodes(&["elif", "else", "endif"])?;
+            let (end, end_line) = end.unwrap_or_default();
+            branches.push((condition, body));
+
+            if let Some(args) = end.strip_prefix("elif") {
+                condition = self.parse_condition(args.trim(), end_line)?;
+            } else if end == "else" {
+                let (body, _) = self.parse_nodes(&["endif"])?;
+                otherwise = body;
+                break;
+            } else {
+                break;
+            }
+        }
+
+        return Ok(Node::If {
+            branches,
+            otherwise,
+            line,
+        });
+    }
+
+    fn parse_for(&mut self, args: &str, line: usize) -> Result<Node, SiteError> {
+        let v: Vec<&str> = args.splitn(3, char::is_whitespace).collect();
+        let (name, expr) = match v.as_slice() {
+            [name, "in", expr] if !name.contains('.') => (name, expr),
+            _ => return Err(self.error(line, "Expected '{% for <name> in <list> %}'.")),
+        };
+        let expr = self.parse_condition(expr, line)?;
+        let (body, _) = self.parse_nodes(&["endfor"])?;
+
+        return Ok(Node::For {
+            name: String::from(*name),
+            expr,
+            body,
+            line,
+        });
+    }
+
+    fn parse_block(&mut self, args: &str, line: usize) -> Result<Node, SiteError> {
+        if args.is_empty() || args.contains(char::is_whitespace) {
+            return Err(self.error(line, "Expected '{% block <name> %}'."));
+        }
+        if self.blocks.contains_key(args) {
+            return Err(self.error(line, &format!("Block '{}' is defined twice.", args)));
+        }
+
+        let (body, _) = self.parse_nodes(&["endblock"])?;
+        self.blocks.insert(String::from(args), body);
+
+        return Ok(Node::Block {
+            name: String::from(args),
+        });
+    }
+
+    fn parse_condition(&self, args: &str, line: usize) -> Result<Expr, SiteError> {
+        let tokens = tokenize(args).map_err(|e| self.error(line, &e))?;
+        return parse_expression(&tokens).map_err(|e| self.error(line, &e));
+    }
+
+    /// Parses the quoted template name of 'include' and 'extends'.
+    fn parse_name(&self, args: &str, line: usize) -> Result<String, SiteError> {
+        return match tokenize(args).as_deref() {
+            Ok([Token::String(name)]) => Ok(name.clone()),
+            _ => Err(self.error(line, "Expected a quoted template name.")),
+        };
+    }
+
+    /// Line of the last tag or output, text after it is not counted.
+    fn get_last_line(&self) -> usize {
+        for segment in self.segments.iter().rev() {
+            if let Segment::Output(_, line) | Segment::Tag(_, line) = segment {
+                return *line;
+            }
+        }
+
+        return 1;
+    }
+
+    fn error(&self, line: usize, message: &str) -> SiteError {
+        return SiteError::parse(self.path, Some(line), message);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    String(String),
+    Equal,
+    NotEqual,
+    Pipe,
+}
+
+fn tokenize(str: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = str.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some(next) if next == c => break,
+                    Some(next) => value.push(next),
+                    _ => return Err(String::from("Missing closing quote.")),
+                }
+            }
+            tokens.push(Token::String(value));
+        } else if c == '|' {
+            tokens.push(Token::Pipe);
+        } else if (c == '=' || c == '!') && chars.peek() == Some(&'=') {
+            chars.next();
+            tokens.push(if c == '=' {
+                Token::Equal
+            } else {
+                Token::NotEqual
+            });
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut word = String::from(c);
+            while let Some(next) = chars.peek() {
+                if !next.is_alphanumeric() && *next != '_' && *next != '.' {
+                    break;
+                }
+                word.push(*next);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        } else {
+            return Err(format!("Unexpected character: '{}'.", c));
+        }
+    }
+
+    return Ok(tokens);
+}
+
+/// Parses `a or b`, `a and b`, `not a`, `a == b`, `a != b`, quoted strings, numbers and
+/// paths like `post.title`, in that order of precedence.
+fn parse_expression(tokens: &[Token]) -> Result<Expr, String> {
+    if tokens.is_empty() {
+        return Err(String::from("Missing expression."));
+    }
+
+    let is_word = |token: &Token, word: &str| *token == Token::Word(String::from(word));
+    if let Some(i) = tokens.iter().rposition(|t| is_word(t, "or")) {
+        return Ok(Expr::Or(
+            Box::new(parse_expression(&tokens[..i])?),
+            Box::new(parse_expression(&tokens[i + 1..])?),
+        ));
+    }
+    if let Some(i) = tokens.iter().rposition(|t| is_word(t, "and")) {
+        return Ok(Expr::And(
+            Box::new(parse_expression(&tokens[..i])?),
+            Box::new(parse_expression(&tokens[i + 1..])?),
+        ));
+    }
+    if is_word(&tokens[0], "not") {
+        return Ok(Expr::Not(Box::new(parse_expression(&tokens[1..])?)));
+    }
+    if let Some(i) = tokens
+        .iter()
+        .position(|t| *t == Token::Equal || *t == Token::NotEqual)
+    {
+        let left = Box::new(parse_expression(&tokens[..i])?);
+        let right = Box::new(parse_expression(&tokens[i + 1..])?);
+        if tokens[i] == Token::Equal {
+            return Ok(Expr::Equal(left, right));
+        }
+        return Ok(Expr::NotEqual(left, right));
+    }
+
+    return match tokens {
+        [Token::String(value)] => Ok(Expr::String(value.clone())),
+        [Token::Word(word)] if word.chars().all(|c| c.is_ascii_digit()) => {
+            Ok(Expr::Int(word.parse().map_err(|_| "Number is too big.")?))
+        }
+        [Token::Word(word)] if word.split('.').all(|part| !part.is_empty()) => {
+            Ok(Expr::Path(word.split('.').map(String::from).collect()))
+        }
+        _ => Err(String::from("Unable to parse expression.")),
+    };
+}
+
+/// All templates of a theme by name (path relative to the templates dir, e.g.
+/// 'partials/header.html'), templates include and extend each other by these names.
+pub struct Templates {
+    templates: HashMap<String, Template>,
+}
+
+struct RenderState<'a> {
+    template: &'a Template,
+    context: &'a Value,
+    scopes: Vec<(String, Value)>,
+    blocks: HashMap<&'a str, (&'a Template, &'a [Node])>,
+    depth: usize,
+}
+
+impl Templates {
+    pub fn new() -> Self {
+        Self {
+            templates: HashMap::new(),
+        }
+    }
+
+    /// Adds `template` as `name`, replacing a template with the same name.
+    pub fn insert(&mut self, name: String, template: Template) {
+        self.templates.insert(name, template);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        return self.templates.contains_key(name);
+    }
+
+    /// Renders template `name` with the data in `context`.
+    pub fn render(&self, name: &str, context: &Value) -> Result<String, SiteError> {
+        let template = match self.templates.get(name) {
+            Some(value) => value,
+            _ => {
+                return Err(SiteError::content(&format!(
+                    "Did not find template: '{}'.",
+                    name
+                )));
+            }
+        };
+
+        let mut output = String::new();
+        let mut state = RenderState {
+            template,
+            context,
+            scopes: Vec::new(),
+            blocks: HashMap::new(),
+            depth: 0,
+        };
+        self.render_template(&mut state, &mut output)?;
+
+        return Ok(output);
+    }
+
+    /// Renders `state.template`, or the template it extends with its blocks replaced.
+    fn render_template<'a>(
+        &'a self,
+        state: &mut RenderState<'a>,
+        output: &mut String,
+    ) -> Result<(), SiteError> {
+        let template = state.template;
+        let (parent_name, line) = match &template.parent {
+            Some(value) => value,
+            _ => return self.render_nodes(state, &template.nodes, output),
+        };
+
+        // blocks of the most derived template win, so only add the ones not set yet:
+        for (name, nodes) in &template.blocks {
+            state
+                .blocks
+                .entry(name.as_str())
+                .or_insert((template, nodes.as_slice()));
+        }
+
+        state.template = self.get_template(template, parent_name, *line, state.depth)?;
+        state.depth += 1;
+        return self.render_template(state, output);
+    }
+
+    fn render_nodes<'a>(
+        &'a self,
+        state: &mut RenderState<'a>,
+        nodes: &'a [Node],
+        output: &mut String,
+    ) -> Result<(), SiteError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Output {
+                    expr,
+                    filters,
+                    line,
+                } => {
+                    let value = self.evaluate(state, expr, *line)?;
+                    let mut str = match value {
+                        Value::Null => String::new(),
+                        Value::Bool(value) => value.to_string(),
+                        Value::Int(value) => value.to_string(),
+                        Value::String(value) => value,
+                        _ => {
+                            return Err(self.error(
+                                state,
+                                *line,
+                                "Unable to output a list or map.",
+                            ));
+                        }
+                    };
+
+                    for filter in filters {
+                        if filter == "lower" {
+                            str = str.to_lowercase();
+                        } else if filter == "upper" {
+                            str = str.to_uppercase();
+                        }
+                    }
+                    if filters.iter().any(|filter| filter == "safe") {
+                        output.push_str(&str);
+                    } else {
+                        escape(&str, output);
+                    }
+                }
+                Node::If {
+                    branches,
+                    otherwise,
+                    line,
+                } => {
+                    let mut body = otherwise;
+                    for (condition, branch) in branches {
+                        if self.evaluate(state, condition, *line)?.is_true() {
+                            body = branch;
+                            break;
+                        }
+                    }
+                    self.render_nodes(state, body, output)?;
+                }
+                Node::For {
+                    name,
+                    expr,
+                    body,
+                    line,
+                } => {
+                    let items = match self.evaluate(state, expr, *line)? {
+                        Value::List(value) => value,
+                        Value::Null => Vec::new(),
+                        _ => {
+                            return Err(self.error(
+                                state,
+                                *line,
+                                "Unable to loop over a non list.",
+                            ));
+                        }
+                    };
+
+                    let num_items = items.len();
+                    for (i, item) in items.into_iter().enumerate() {
+                        let info = Value::map(vec![
+                            ("index", Value::from(i + 1)),
+                            ("first", Value::from(i == 0)),
+                            ("last", Value::from(i + 1 == num_items)),
+                        ]);
+                        state.scopes.push((String::from("loop"), info));
+                        state.scopes.push((name.clone(), item));
+                        let result = self.render_nodes(state, body, output);
+                        state.scopes.truncate(state.scopes.len() - 2);
+                        result?;
+                    }
+                }
+                Node::Include { name, line } => {
+                    let template = self.get_template(state.template, name, *line, state.depth)?;
+                    let mut include_state = RenderState {
+                        template,
+                        context: state.context,
+                        scopes: state.scopes.clone(),
+                        blocks: HashMap::new(),
+                        depth: state.depth + 1,
+                    };
+                    self.render_template(&mut include_state, output)?;
+                }
+                Node::Block { name } => {
+                    let own = (state.template, &state.template.blocks[name][..]);
+                    let (template, nodes) = state.blocks.get(name.as_str()).copied().unwrap_or(own);
+
+                    // errors in a block are reported for the template it's defined in:
+                    let current = state.template;
+                    state.template = template;
+                    let result = self.render_nodes(state, nodes, output);
+                    state.template = current;
+                    result?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn evaluate(&self, state: &RenderState, expr: &Expr, line: usize) -> Result<Value, SiteError> {
+        return match expr {
+            Expr::Path(path) => self.lookup(state, path, line),
+            Expr::String(value) => Ok(Value::String(value.clone())),
+            Expr::Int(value) => Ok(Value::Int(*value)),
+            Expr::Not(value) => Ok(Value::Bool(!self.evaluate(state, value, line)?.is_true())),
+            Expr::Equal(left, right) => Ok(Value::Bool(
+                self.evaluate(state, left, line)? == self.evaluate(state, right, line)?,
+            )),
+            Expr::NotEqual(left, right) => Ok(Value::Bool(
+                self.evaluate(state, left, line)? != self.evaluate(state, right, line)?,
+            )),
+            Expr::And(left, right) => Ok(Value::Bool(
+                self.evaluate(state, left, line)?.is_true()
+                    && self.evaluate(state, right, line)?.is_true(),
+            )),
+            Expr::Or(left, right) => Ok(Value::Bool(
+                self.evaluate(state, left, line)?.is_true()
+                    || self.evaluate(state, right, line)?.is_true(),
+            )),
+        };
+    }
+
+    /// Looks up `path` in the loop variables first (innermost first), then in the
+    /// context.
+    fn lookup(
+        &self,
+        state: &RenderState,
+        path: &[String],
+        line: usize,
+    ) -> Result<Value, SiteError> {
+        let (mut value, keys) = match state.scopes.iter().rev().find(|(name, _)| *name == path[0]) {
+            Some((_, value)) => (value, &path[1..]),
+            _ => (state.context, path),
+        };
+
+        for key in keys {
+            value = match value {
+                Value::Map(map) if map.contains_key(key) => &map[key],
+                // fields of optional values (e.g. 'topic_info') are null as well:
+                Value::Null => return Ok(Value::Null),
+                _ => {
+                    return Err(self.error(
+                        state,
+                        line,
+                        &format!("Unknown variable: '{}'.", path.join(".")),
+                    ));
+                }
+            };
+        }
+
+        return Ok(value.clone());
+    }
+
+    fn get_template<'a>(
+        &'a self,
+        current: &Template,
+        name: &str,
+        line: usize,
+        depth: usize,
+    ) -> Result<&'a Template, SiteError> {
+        if depth >= MAX_DEPTH {
+            return Err(SiteError::parse(
+                &current.path,
+                Some(line),
+                "Templates include or extend each other endlessly.",
+            ));
+        }
+
+        return match self.templates.get(name) {
+            Some(value) => Ok(value),
+            _ => Err(SiteError::parse(
+                &current.path,
+                Some(line),
+                &format!("Did not find template: '{}'.", name),
+            )),
+        };
+    }
+
+    fn error(&self, state: &RenderState, line: usize, message: &str) -> SiteError {
+        return SiteError::parse(&state.template.path, Some(line), message);
+    }
+}
+
+/// Appends `str` to `output` with html special characters escaped, the same as the
+/// compiled templates do.
+fn escape(str: &str, output: &mut String) {
+    for c in str.chars() {
+        match c {
+            '"' => output.push_str("&quot;"),
+            '&' => output.push_str("&amp;"),
+            '\'' => output.push_str("&#039;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            _ => output.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `sources` (name, source) into templates, panics on parse errors.
+    fn get_templates(sources: &[(&str, &str)]) -> Templates {
+        let mut templates = Templates::new();
+        for (name, source) in sources {
+            templates.insert(
+                String::from(*name),
+                Template::parse(Path::new(name), source).unwrap(),
+            );
+        }
+
+        return templates;
+    }
+
+    fn render(source: &str, context: &Value) -> String {
+        return get_templates(&[("page.html", source)])
+            .render("page.html", context)
+            .unwrap();
+    }
+
+    fn get_parse_error(source: &str) -> String {
+        return Template::parse(Path::new("page.html"), source)
+            .unwrap_err()
+            .to_string();
+    }
+
+    #[test]
+    fn parse_errors_name_template_and_line() {
+        assert_eq!(
+            get_parse_error("<p>\n{{ title\n</p>"),
+            "page.html:2: Missing '}}' for '{{'."
+        );
+        assert_eq!(
+            get_parse_error("<ul>\n{% for post in posts %}\n<li>"),
+            "page.html:2: Missing '{% endfor %}'."
+        );
+        assert_eq!(
+            get_parse_error("{% if a %}\n\n{% endfor %}"),
+            "page.html:3: Unexpected '{% endfor %}'."
+        );
+        assert_eq!(
+            get_parse_error("\n\n\n{% endif %}"),
+            "page.html:4: Unexpected '{% endif %}'."
+        );
+        assert_eq!(
+            get_parse_error("{# a\ncomment #}\n{{ title | bold }}"),
+            "page.html:3: Unknown filter."
+        );
+        assert_eq!(
+            get_parse_error("{% for post of posts %}{% endfor %}"),
+            "page.html:1: Expected '{% for <name> in <list> %}'."
+        );
+        assert_eq!(
+            get_parse_error("{% if a %}a\n{% else b %}b{% endif %}"),
+            "page.html:2: Unexpected '{% else b %}'."
+        );
+        assert_eq!(
+            get_parse_error("{% for a in b %}{% endfor a %}"),
+            "page.html:1: Unexpected '{% endfor a %}'."
+        );
+        assert_eq!(
+            get_parse_error("{% block a %}{% endblock %}\n{% block a %}{% endblock %}"),
+            "page.html:2: Block 'a' is defined twice."
+        );
+    }
+
+    #[test]
+    fn render_errors_name_template_and_line() {
+        let templates = get_templates(&[
+            (
+                "base.html",
+                "<main>\n{% block content %}{% endblock %}</main>",
+            ),
+            (
+                "page.html",
+                "{% extends \"base.html\" %}\n{% block content %}\n{{ post.title }}{% endblock %}",
+            ),
+            ("missing.html", "\n{% include \"partials/missing.html\" %}"),
+        ]);
+
+        let error = templates.render("page.html", &Value::map(vec![]));
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "page.html:3: Unknown variable: 'post.title'."
+        );
+
+        let error = templates.render("missing.html", &Value::map(vec![]));
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "missing.html:2: Did not find template: 'partials/missing.html'."
+        );
+    }
+
+    #[test]
+    fn elif_chain_renders_first_true_branch() {
+        let source = "{% if kind == \"a\" %}A{% elif kind == \"b\" %}B\
+                      {% elif kind == \"c\" or other %}C{% else %}D{% endif %}";
+        let context = |kind: &str, other: bool| {
+            return Value::map(vec![
+                ("kind", Value::from(kind)),
+                ("other", Value::from(other)),
+            ]);
+        };
+
+        assert_eq!(render(source, &context("a", true)), "A");
+        assert_eq!(render(source, &context("b", true)), "B");
+        assert_eq!(render(source, &context("c", false)), "C");
+        assert_eq!(render(source, &context("x", true)), "C");
+        assert_eq!(render(source, &context("x", false)), "D");
+    }
+
+    #[test]
+    fn empty_and_missing_values_are_false() {
+        let source = "{% if value %}yes{% else %}no{% endif %}";
+
+        assert_eq!(
+            render(source, &Value::map(vec![("value", Value::from(""))])),
+            "no"
+        );
+        assert_eq!(
+            render(
+                source,
+                &Value::map(vec![("value", Value::from(Vec::new()))])
+            ),
+            "no"
+        );
+        assert_eq!(
+            render(source, &Value::map(vec![("value", Value::Null)])),
+            "no"
+        );
+        assert_eq!(
+            render(source, &Value::map(vec![("value", Value::from(0))])),
+            "no"
+        );
+        assert_eq!(
+            render(source, &Value::map(vec![("value", Value::from("x"))])),
+            "yes"
+        );
+        assert_eq!(
+            render(
+                "{% if not value.field %}no{% endif %}",
+                &Value::map(vec![("value", Value::Null)])
+            ),
+            "no"
+        );
+    }
+
+    #[test]
+    fn loop_variables() {
+        let source = "{% for tag in tags %}{% if loop.first %}[{% endif %}\
+                      {{ loop.index }}:{{ tag }}{% if loop.last %}]{% else %},{% endif %}\
+                      {% endfor %}";
+        let context = Value::map(vec![(
+            "tags",
+            Value::from(vec![Value::from("a"), Value::from("b"), Value::from("c")]),
+        )]);
+
+        assert_eq!(render(source, &context), "[1:a,2:b,3:c]");
+        assert_eq!(render(source, &Value::map(vec![("tags", Value::Null)])), "");
+    }
+
+    #[test]
+    fn nested_loops_shadow_loop_variables() {
+        let source = "{% for a in outer %}{% for b in inner %}{{ loop.index }}{% endfor %}\
+                      {{ loop.index }};{% endfor %}";
+        let context = Value::map(vec![
+            (
+                "outer",
+                Value::from(vec![Value::from("x"), Value::from("y")]),
+            ),
+            (
+                "inner",
+                Value::from(vec![Value::from("x"), Value::from("y")]),
+            ),
+        ]);
+
+        assert_eq!(render(source, &context), "121;122;");
+    }
+
+    #[test]
+    fn output_is_escaped_unless_safe() {
+        let context = Value::map(vec![("html", Value::from("<a href=\"x\">'R&D'</a>"))]);
+
+        assert_eq!(
+            render("{{ html }}", &context),
+            "&lt;a href=&quot;x&quot;&gt;&#039;R&amp;D&#039;&lt;/a&gt;"
+        );
+        assert_eq!(
+            render("{{ html | safe }}", &context),
+            "<a href=\"x\">'R&D'</a>"
+        );
+        assert_eq!(
+            render("{{ html | upper | safe }}", &context),
+            "<A HREF=\"X\">'R&D'</A>"
+        );
+        assert_eq!(
+            render(
+                "{{ html | lower }}",
+                &Value::map(vec![("html", Value::from("<B>"))])
+            ),
+            "&lt;b&gt;"
+        );
+    }
+
+    #[test]
+    fn most_derived_block_wins() {
+        let templates = get_templates(&[
+            (
+                "base.html",
+                "<title>{% block title %}Site{% endblock %}</title>\
+                 {% block content %}empty{% endblock %}",
+            ),
+            (
+                "page.html",
+                "{% extends \"base.html\" %}{% block content %}page{% endblock %}",
+            ),
+            (
+                "post.html",
+                "{% extends \"page.html\" %}{% block title %}{{ title }}{% endblock %}\
+                 {% block content %}{% include \"partials/post.html\" %}{% endblock %}",
+            ),
+            ("partials/post.html", "<p>{{ title }}</p>"),
+        ]);
+        let context = Value::map(vec![("title", Value::from("Post"))]);
+
+        assert_eq!(
+            templates.render("base.html", &context).unwrap(),
+            "<title>Site</title>empty"
+        );
+        assert_eq!(
+            templates.render("page.html", &context).unwrap(),
+            "<title>Site</title>page"
+        );
+        assert_eq!(
+            templates.render("post.html", &context).unwrap(),
+            "<title>Post</title><p>Post</p>"
+        );
+    }
+
+    #[test]
+    fn endless_extends_is_an_error() {
+        let templates = get_templates(&[
+            ("a.html", "{% extends \"b.html\" %}"),
+            ("b.html", "{% extends \"a.html\" %}"),
+        ]);
+
+        let error = templates.render("a.html", &Value::map(vec![]));
+        assert!(
+            error
+                .unwrap_err()
+                .to_string()
+                .ends_with("Templates include or extend each other endlessly.")
+        );
+    }
+}
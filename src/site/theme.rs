@@ -0,0 +1,228 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use std::path::{Path, PathBuf};
+
+use crate::site::config::SITE_URL;
+use crate::site::error::SiteError;
+use crate::site::helper::Helper;
+use crate::site::menu::Menu;
+use crate::site::paths::SitePaths;
+use crate::site::template::{Template, Templates, Value};
+
+/// Theme every other theme falls back to for templates it doesn't override.
+pub const DEFAULT_THEME: &str = "default";
+
+/// Layouts of pages the compiled 'page.stpl' knows, themes can add more.
+const COMPILED_LAYOUTS: [&str; 2] = ["page", "error"];
+
+/// A page that can be rendered with the compiled templates in 'templates' or with the
+/// runtime templates of a theme.
+pub trait ThemeTemplate: sailfish::Template {
+    /// Name of the runtime template in the theme, e.g. 'blog_post.html'.
+    fn get_template_name(&self) -> &str;
+
+    /// Header menu shown on the page.
+    fn get_menu(&self) -> &Menu;
+
+    /// Data the runtime template is rendered with.
+    fn get_context(&self) -> Value;
+}
+
+/// Renders pages with the compiled templates, or with the runtime templates in
+/// 'themes/<name>/templates' if a theme is set in 'site.conf'. Templates missing in the
+/// theme are taken from the default theme, so a theme can override single partials.
+pub struct Theme {
+    name: Option<String>,
+    templates: Templates,
+    build_data: Vec<u8>,
+}
+
+impl Theme {
+    /// Loads theme `name` of the site at `paths`, `None` uses the compiled templates.
+    pub fn load(paths: &SitePaths, name: &Option<String>) -> Result<Self, SiteError> {
+        let mut theme = Self {
+            name: name.clone(),
+            templates: Templates::new(),
+            build_data: Vec::new(),
+        };
+        let name = match name {
+            Some(value) => value,
+            _ => return Ok(theme),
+        };
+
+        let theme_dir = paths.get_themes_dir().join(name);
+        if !Helper::exists_dir(&theme_dir) {
+            return Err(SiteError::content(&format!(
+                "Did not find theme: '{}' in '{}'.",
+                name,
+                paths.get_themes_dir().display()
+            )));
+        }
+
+        // default theme first, so the templates of the theme replace its ones:
+        let mut dirs = vec![paths.get_themes_dir().join(DEFAULT_THEME)];
+        if name != DEFAULT_THEME {
+            dirs.push(theme_dir);
+        }
+        for dir in dirs {
+            theme.add_templates(&dir.join("templates"))?;
+        }
+
+        return Ok(theme);
+    }
+
+    /// Templates are part of the build hash of the cache, pages are rendered again when
+    /// one of them changes.
+    pub fn get_build_data(&self) -> &[u8] {
+        return &self.build_data;
+    }
+
+    /// Static files of the theme, copied to 'static' in the output dir over the ones of
+    /// the site.
+    pub fn get_static_dir(&self, paths: &SitePaths) -> Option<PathBuf> {
+        let dir = paths
+            .get_themes_dir()
+            .join(self.name.as_ref()?)
+            .join("static");
+        if !Helper::exists_dir(&dir) {
+            return None;
+        }
+
+        return Some(dir);
+    }
+
+    /// Returns true if pages can use layout `name`, i.e. 'templates/layouts/<name>.html'
+    /// exists in the theme.
+    pub fn has_layout(&self, name: &str) -> bool {
+        if self.name.is_none() {
+            return COMPILED_LAYOUTS.contains(&name);
+        }
+
+        return self.templates.contains(&get_layout_template_name(name));
+    }
+
+    /// Renders `page` to be written to `output_file`.
+    pub fn render<T: ThemeTemplate>(
+        &self,
+        page: &T,
+        output_file: &Path,
+    ) -> Result<String, SiteError> {
+        if self.name.is_none() {
+            return page.render().map_err(|e| SiteError::render(output_file, e));
+        }
+
+        // available in every template:
+        let mut context = page.get_context();
+        context.insert("site_url", Value::from(SITE_URL));
+        context.insert("menu", page.get_menu().get_context());
+
+        return self.templates.render(page.get_template_name(), &context);
+    }
+
+    fn add_templates(&mut self, dir: &Path) -> Result<(), SiteError> {
+        if !Helper::exists_dir(dir) {
+            return Ok(());
+        }
+
+        let mut files = Helper::get_files_recursive(dir)?;
+        files.sort();
+        for file in files {
+            let source =
+                std::fs::read_to_string(&file).map_err(|e| SiteError::io("read", &file, e))?;
+
+            // templates include and extend each other by their path in the templates dir:
+            let name = Helper::get_relative_path(&file, dir)?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            self.build_data.extend_from_slice(name.as_bytes());
+            self.build_data.extend_from_slice(source.as_bytes());
+            self.templates
+                .insert(name, Template::parse(&file, &source)?);
+        }
+
+        Ok(())
+    }
+}
+
+/// Name of the runtime template of page layout `name`.
+pub fn get_layout_template_name(name: &str) -> String {
+    return format!("layouts/{}.html", name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::site::helper::Verbosity;
+    use crate::site::{BuildOptions, generate_site};
+
+    /// Generates the site in the crate root into `output_dir` with theme `theme` (none for
+    /// the compiled templates).
+    async fn generate_test_site(output_dir: &Path, theme: &str) -> Result<(), SiteError> {
+        let mut paths = SitePaths::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")));
+        let config = std::fs::read_to_string(&paths.config_file).unwrap();
+        let mut config: Vec<&str> = config
+            .lines()
+            .filter(|line| !line.starts_with("theme:"))
+            .collect();
+        let theme = format!("theme: {}", theme);
+        config.push(&theme);
+
+        std::fs::create_dir_all(output_dir).unwrap();
+        paths.config_file = output_dir.join("site.conf");
+        std::fs::write(&paths.config_file, config.join("\n")).unwrap();
+        paths.output_dir = output_dir.join("output");
+
+        return generate_site(BuildOptions {
+            paths,
+            use_cache: false,
+            include_drafts: true,
+        })
+        .await;
+    }
+
+    /// The default theme is the compiled templates as runtime templates, both must render
+    /// every page the same (apart from whitespace).
+    #[tokio::test(flavor = "multi_thread")]
+    async fn default_theme_renders_as_compiled_templates() {
+        Helper::set_verbosity(Verbosity::Quiet);
+        let test_dir = std::env::temp_dir().join(format!("flow-web-theme-{}", std::process::id()));
+        let compiled_dir = test_dir.join("compiled");
+        let theme_dir = test_dir.join("theme");
+        generate_test_site(&compiled_dir, "").await.unwrap();
+        generate_test_site(&theme_dir, DEFAULT_THEME).await.unwrap();
+
+        let compiled_output_dir = compiled_dir.join("output");
+        let theme_output_dir = theme_dir.join("output");
+        let mut files = Helper::get_files_recursive(&compiled_output_dir).unwrap();
+        files.sort();
+        let mut num_pages = 0;
+        let mut differing_files = Vec::new();
+        for file in files {
+            if file.extension().is_none_or(|extension| extension != "html") {
+                continue;
+            }
+
+            let relative_path = Helper::get_relative_path(&file, &compiled_output_dir).unwrap();
+            let without_whitespace = |path: &Path| {
+                return std::fs::read_to_string(path)
+                    .unwrap()
+                    .split_whitespace()
+                    .collect::<String>();
+            };
+            if without_whitespace(&file)
+                != without_whitespace(&theme_output_dir.join(relative_path))
+            {
+                differing_files.push(relative_path.to_string_lossy().into_owned());
+            }
+            num_pages += 1;
+        }
+
+        std::fs::remove_dir_all(&test_dir).unwrap();
+        assert!(num_pages > 0);
+        assert_eq!(differing_files, Vec::<String>::new());
+    }
+}
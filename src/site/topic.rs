@@ -0,0 +1,155 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use std::path::PathBuf;
+
+use crate::site::error::SiteError;
+use crate::site::helper::Helper;
+
+/// Optional extra content for a topic page, read from 'topics/<slug>.md'.
+#[derive(Clone, Debug)]
+pub struct TopicInfo {
+    pub slug: String,
+    pub description: String,
+    pub image: String,
+    pub pinned: Vec<String>,
+    pub html: String,
+}
+
+impl TopicInfo {
+    pub fn new(slug: String) -> Self {
+        Self {
+            slug,
+            description: String::new(),
+            image: String::new(),
+            pinned: Vec::new(),
+            html: String::new(),
+        }
+    }
+
+    /// Url of the image shown above the topic description, 'image' is relative to the
+    /// site root with or without a leading '/' (or an absolute url). Empty without image.
+    pub fn get_image_url(&self) -> String {
+        if self.image.is_empty()
+            || self.image.starts_with('/')
+            || self.image.starts_with("http://")
+            || self.image.starts_with("https://")
+        {
+            return self.image.clone();
+        }
+
+        return format!("/{}", self.image);
+    }
+}
+
+/// Trims every '/' separated part of a topic and drops parts without a url, so that e.g.
+/// "Hardware / Coreboot" and "Hardware/Coreboot" end up as the same topic and every part
+/// matches one part of its url (`Helper::sanitize_path`).
+pub fn normalize_topic(topic: &str) -> String {
+    return Helper::get_path_parts(topic).join("/");
+}
+
+/// Returns the topic itself and all of its parents, top most parent first.
+/// "Hardware/Coreboot" gives ["Hardware", "Hardware/Coreboot"].
+pub fn get_topic_with_parents(topic: &str) -> Vec<String> {
+    let mut topics = Vec::new();
+    let mut current = String::new();
+    for part in topic.split('/') {
+        if !current.is_empty() {
+            current += "/";
+        }
+        current += part;
+        topics.push(current.clone());
+    }
+
+    return topics;
+}
+
+/// Returns the direct parent of a topic, if any.
+pub fn get_topic_parent(topic: &str) -> Option<&str> {
+    return topic.rsplit_once('/').map(|(parent, _name)| parent);
+}
+
+/// Returns the last part of a topic, e.g. "Coreboot" for "Hardware/Coreboot".
+pub fn get_topic_name(topic: &str) -> &str {
+    return match topic.rsplit_once('/') {
+        Some((_parent, name)) => name,
+        _ => topic,
+    };
+}
+
+pub async fn parse_topic_file(path: PathBuf, topics_dir: PathBuf) -> Result<TopicInfo, SiteError> {
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| SiteError::io("read", &path, e))?;
+    let mut body_start_found = false;
+    let mut markdown = String::new();
+
+    // the file path (without extension) relative to 'topics' is the sanitized topic
+    // name, sub topics reside in sub folders e.g. 'topics/coreboot/thinkpad.md':
+    let slug = match path.strip_prefix(&topics_dir) {
+        Ok(value) => Helper::sanitize_path(&value.with_extension("").to_string_lossy()),
+        _ => {
+            return Err(SiteError::parse(
+                &path,
+                None,
+                "Unable to get topic name from path.",
+            ));
+        }
+    };
+    let mut topic_info = TopicInfo::new(slug);
+
+    for (i, line) in contents.lines().enumerate() {
+        let error = |message: &str| SiteError::parse(&path, Some(i + 1), message);
+        if !body_start_found && !line.is_empty() {
+            if line == "---" {
+                body_start_found = true;
+                continue;
+            }
+
+            let v: Vec<&str> = line.splitn(2, ':').collect();
+            if v.len() != 2 {
+                return Err(error("Expected a line like 'key: value'."));
+            }
+
+            if let Some(key) = v.first() {
+                if *key == "description" {
+                    if let Some(value) = v.get(1) {
+                        topic_info.description = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'description'."));
+                    }
+                } else if *key == "image" {
+                    if let Some(value) = v.get(1) {
+                        topic_info.image = String::from(value.trim());
+                    } else {
+                        return Err(error("Unable to parse field: 'image'."));
+                    }
+                } else if *key == "pinned" {
+                    if let Some(value) = v.get(1) {
+                        for url in value.split(',') {
+                            let url_trimmed = url.trim();
+                            if !url_trimmed.is_empty() {
+                                topic_info.pinned.push(String::from(url_trimmed));
+                            }
+                        }
+                    } else {
+                        return Err(error("Unable to parse field: 'pinned'."));
+                    }
+                } else {
+                    return Err(error(&format!("Unknown topic field: '{}'.", key)));
+                }
+            }
+        } else {
+            if !markdown.is_empty() {
+                markdown += "\n";
+            }
+            markdown += line;
+        }
+    }
+
+    // convert markdown to html:
+    topic_info.html = markdown::to_html(&markdown);
+
+    return Ok(topic_info);
+}
@@ -0,0 +1,122 @@
+// luflow.net web site
+// AGPL-3.0 License (see LICENSE)
+
+use crate::site::error::SiteError;
+
+/// Minimal XML writer that takes care of escaping, indentation and closing elements in
+/// the right order.
+pub struct XmlWriter {
+    data: String,
+    open_elements: Vec<String>,
+    error: Option<String>,
+}
+
+impl XmlWriter {
+    pub fn new() -> Self {
+        Self {
+            data: String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n"),
+            open_elements: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Opens element `name`, must be closed with `end`.
+    pub fn start(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.write_indent();
+        self.data += "<";
+        self.data += name;
+        self.write_attributes(attributes);
+        self.data += ">\n";
+
+        self.open_elements.push(String::from(name));
+    }
+
+    /// Closes the element opened last, closing more elements than were opened makes
+    /// `finish` fail.
+    pub fn end(&mut self) {
+        let name = match self.open_elements.pop() {
+            Some(value) => value,
+            _ => {
+                if self.error.is_none() {
+                    self.error = Some(String::from("No open XML element to close."));
+                }
+                return;
+            }
+        };
+
+        self.write_indent();
+        self.data += "</";
+        self.data += &name;
+        self.data += ">\n";
+    }
+
+    /// Writes element `name` with escaped `text` as its content.
+    pub fn element(&mut self, name: &str, attributes: &[(&str, &str)], text: &str) {
+        self.write_indent();
+        self.data += "<";
+        self.data += name;
+        self.write_attributes(attributes);
+        self.data += ">";
+        self.data += &escape_xml(text);
+        self.data += "</";
+        self.data += name;
+        self.data += ">\n";
+    }
+
+    /// Writes element `name` without any content.
+    pub fn empty(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.write_indent();
+        self.data += "<";
+        self.data += name;
+        self.write_attributes(attributes);
+        self.data += "/>\n";
+    }
+
+    /// Returns the written document, if every element was closed exactly once.
+    pub fn finish(self) -> Result<String, SiteError> {
+        if let Some(message) = &self.error {
+            return Err(SiteError::content(message));
+        }
+        if let Some(name) = self.open_elements.last() {
+            return Err(SiteError::content(&format!(
+                "XML element: '{}' was never closed.",
+                name
+            )));
+        }
+
+        return Ok(self.data);
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.open_elements.len() {
+            self.data += "    ";
+        }
+    }
+
+    fn write_attributes(&mut self, attributes: &[(&str, &str)]) {
+        for (key, value) in attributes {
+            self.data += " ";
+            self.data += key;
+            self.data += "=\"";
+            self.data += &escape_xml(value);
+            self.data += "\"";
+        }
+    }
+}
+
+/// Escapes the characters that are not allowed as is in XML text and attribute values.
+pub fn escape_xml(str: &str) -> String {
+    let mut escaped = String::with_capacity(str.len());
+    for c in str.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&apos;",
+            c => escaped.push(c),
+        }
+    }
+
+    return escaped;
+}
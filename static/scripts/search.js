@@ -0,0 +1,129 @@
+/* luflow.net web site */
+/* Public domain 2025. All rights waived */
+
+// searches the blog posts in '/search/index.json' (generated at build time) entirely in
+// the browser, every word of the query must be found in a post for it to be listed:
+let searchInput = document.getElementById('search-input');
+let searchStatus = document.getElementById('search-status');
+let searchResults = document.getElementById('search-results');
+let searchIndex = null;
+
+function getQueryWords(query) {
+    return query.toLowerCase().split(/[^\p{L}\p{N}]+/u).filter(function (word) {
+        return word.length > 0;
+    });
+}
+
+function getScore(post, queryWords) {
+    let title = post.title.toLowerCase();
+    let topics = post.topics.join(' ').toLowerCase();
+    let snippet = post.snippet.toLowerCase();
+    let words = ' ' + post.words;
+    let score = 0;
+
+    for (let word of queryWords) {
+        let wordScore = 0;
+        if (title.includes(word)) {
+            wordScore += 10;
+        }
+        if (topics.includes(word)) {
+            wordScore += 5;
+        }
+        if (snippet.includes(word)) {
+            wordScore += 3;
+        }
+        if (words.includes(' ' + word)) {
+            wordScore += 1;
+        }
+
+        // all query words must match:
+        if (wordScore == 0) {
+            return 0;
+        }
+        score += wordScore;
+    }
+
+    return score;
+}
+
+function createResult(post) {
+    let link = document.createElement('a');
+    link.className = 'item-preview';
+    link.href = post.url;
+
+    let title = document.createElement('h3');
+    title.lang = 'en';
+    title.textContent = post.title;
+    link.appendChild(title);
+
+    let date = document.createElement('p');
+    date.className = 'item-date';
+    let time = document.createElement('time');
+    time.dateTime = post.datetime;
+    time.textContent = post.date;
+    date.appendChild(time);
+    link.appendChild(date);
+
+    let snippet = document.createElement('p');
+    snippet.className = 'item-summary';
+    snippet.lang = 'en';
+    snippet.textContent = post.snippet;
+    link.appendChild(snippet);
+
+    return link;
+}
+
+function search() {
+    let queryWords = getQueryWords(searchInput.value);
+    searchResults.replaceChildren();
+
+    if (queryWords.length == 0) {
+        searchStatus.textContent = '';
+        return;
+    }
+
+    // posts are sorted latest first, keep that order for posts with the same score:
+    let matches = searchIndex
+        .map(function (post, i) {
+            return { post: post, score: getScore(post, queryWords), order: i };
+        })
+        .filter(function (match) {
+            return match.score > 0;
+        })
+        .sort(function (a, b) {
+            return b.score - a.score || a.order - b.order;
+        });
+
+    if (matches.length == 1) {
+        searchStatus.textContent = '1 blog post found.';
+    } else {
+        searchStatus.textContent = matches.length + ' blog posts found.';
+    }
+    for (let match of matches) {
+        searchResults.appendChild(createResult(match.post));
+    }
+}
+
+// prefill search from '?q=...' so searches can be linked:
+searchInput.value = new URLSearchParams(window.location.search).get('q') || '';
+searchStatus.textContent = 'Loading search index...';
+
+fetch('/search/index.json')
+    .then(function (response) {
+        return response.json();
+    })
+    .then(function (index) {
+        searchIndex = index;
+        searchStatus.textContent = '';
+        search();
+        searchInput.addEventListener('input', function () {
+            // keep url in sync with the query without adding history entries:
+            let url = new URL(window.location);
+            url.searchParams.set('q', searchInput.value);
+            window.history.replaceState(null, '', url);
+            search();
+        });
+    })
+    .catch(function () {
+        searchStatus.textContent = 'Unable to load the search index.';
+    });